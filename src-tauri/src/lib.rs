@@ -1,14 +1,122 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
 use sysinfo::Disks;
 use serde::Serialize;
-use tauri::{Manager, WindowEvent};
+use tauri::{Emitter, Manager, RunEvent, WindowEvent};
+use tauri_plugin_clipboard_manager::ClipboardExt;
 use tauri_plugin_shell::{ShellExt, process::CommandChild};
 use walkdir::WalkDir;
 
-struct ServerState(Mutex<Option<CommandChild>>);
+/// The server process we're responsible for shutting down.
+enum TrackedServer {
+    /// Spawned by this session.
+    Child(CommandChild),
+    /// Left running by a previous session and adopted on startup.
+    Adopted(u32),
+}
 
-#[derive(Serialize)]
+impl TrackedServer {
+    fn pid(&self) -> u32 {
+        match self {
+            Self::Child(child) => child.pid(),
+            Self::Adopted(pid) => *pid,
+        }
+    }
+
+    /// Force-kill the process. Returns whether the kill was delivered.
+    fn kill(self) -> bool {
+        match self {
+            Self::Child(child) => child.kill().is_ok(),
+            Self::Adopted(pid) => force_kill(pid),
+        }
+    }
+}
+
+struct ServerState {
+    server:             Mutex<Option<TrackedServer>>,
+    /// Set whenever we stop the server on purpose, so an exit we caused
+    /// isn't treated as a crash. Cleared when a new spawn is requested.
+    shutdown_requested: AtomicBool,
+    /// Watchdog restarts since the server last came up, so a server that
+    /// dies on every start isn't relaunched forever.
+    crash_restarts:     std::sync::atomic::AtomicU32,
+    /// Held by a `spawn_server` call between its first check and storing
+    /// the child, so concurrent calls can't both launch a server.
+    spawning:           AtomicBool,
+}
+
+/// Clears `ServerState::spawning` however `spawn_server` returns.
+struct SpawningGuard<'a>(&'a AtomicBool);
+
+impl Drop for SpawningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Lock `mutex`, recovering from poisoning. Every mutex here guards plain
+/// values that can't be left half-updated, so one panic while a lock is held
+/// shouldn't turn every later command that touches it into a panic too.
+fn lock_unpoisoned<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| {
+        eprintln!("Mutex was poisoned; recovering");
+        e.into_inner()
+    })
+}
+
+impl ServerState {
+    /// Lock the tracked server, recovering from poisoning, so a panic in an
+    /// earlier holder doesn't make the server unkillable for the session.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<TrackedServer>> {
+        lock_unpoisoned(&self.server)
+    }
+
+    /// Mark the coming exit as requested, then hand over the tracked server
+    /// to be stopped. The flag goes first: the child's exit can reach the
+    /// crash monitor as soon as it is signalled, and must not look like a
+    /// crash.
+    fn begin_shutdown(&self) -> Option<TrackedServer> {
+        self.shutdown_requested.store(true, Ordering::SeqCst);
+        self.lock().take()
+    }
+
+    /// Forget `pid` if it is the tracked server, returning whether it was,
+    /// and decide how the crash monitor answers its exit.
+    fn record_exit(&self, pid: u32, auto_restart: bool) -> (bool, ExitResponse) {
+        let was_tracked = {
+            let mut guard = self.lock();
+            let matches = guard.as_ref().is_some_and(|s| s.pid() == pid);
+            if matches {
+                *guard = None;
+            }
+            matches
+        };
+        let response = exit_response(
+            self.shutdown_requested.load(Ordering::SeqCst),
+            auto_restart,
+            self.crash_restarts.load(Ordering::SeqCst),
+        );
+        (was_tracked, response)
+    }
+}
+
+/// Whether server.conf was absent when the app launched, captured in `setup`
+/// before anything gets a chance to seed it.
+struct FirstRun(bool);
+
+/// Payload of the `setup-progress` event shown by the onboarding screen.
+#[derive(Serialize, Clone)]
+struct SetupProgress {
+    step:  &'static str,
+    label: &'static str,
+}
+
+fn emit_setup_progress(app: &tauri::AppHandle, step: &'static str, label: &'static str) {
+    let _ = app.emit("setup-progress", SetupProgress { step, label });
+}
+
+#[derive(Serialize, Clone)]
 pub struct StorageInfo {
     manga_bytes: u64,
     total_bytes: u64,
@@ -16,9 +124,67 @@ pub struct StorageInfo {
     path:        String,
 }
 
+/// Expand `$VAR` / `${VAR}` (and `%VAR%` on Windows). Unknown variables are
+/// left as written so the resulting path is obviously wrong rather than
+/// silently empty.
+fn expand_env_vars(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(i) = rest.find(['$', '%']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        let (name, consumed) = if let Some(braced) = tail.strip_prefix("${") {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 3),
+                None => ("", 0),
+            }
+        } else if let Some(plain) = tail.strip_prefix('$') {
+            let end = plain
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(plain.len());
+            (&plain[..end], end + 1)
+        } else if cfg!(target_os = "windows") {
+            match tail[1..].find('%') {
+                Some(end) => (&tail[1..end + 1], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            ("", 0)
+        };
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                out.push_str(&value);
+                rest = &tail[consumed..];
+            }
+            _ => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Expand a leading `~` and any environment variables in a user-typed path.
+fn expand_user_path(input: &str) -> PathBuf {
+    let expanded = expand_env_vars(input.trim());
+    let home_rest = if expanded == "~" {
+        Some("")
+    } else {
+        expanded
+            .strip_prefix("~/")
+            .or_else(|| expanded.strip_prefix("~\\"))
+    };
+    match (home_rest, dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(expanded),
+    }
+}
+
 fn resolve_downloads_path(downloads_path: &str) -> PathBuf {
     if !downloads_path.trim().is_empty() {
-        return PathBuf::from(downloads_path);
+        return expand_user_path(downloads_path);
     }
     let base = std::env::var("XDG_DATA_HOME")
         .map(PathBuf::from)
@@ -29,325 +195,6124 @@ fn resolve_downloads_path(downloads_path: &str) -> PathBuf {
     base.join("Tachidesk/downloads")
 }
 
+/// Why a downloads path was refused. Serialised with a `kind` tag so the UI
+/// can show a specific warning instead of a generic error string.
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DownloadsPathError {
+    /// A filesystem root or well-known OS directory.
+    SystemDirectory { path: String },
+    /// The user's home folder itself (subfolders are fine).
+    HomeDirectory { path: String },
+}
+
+impl std::fmt::Display for DownloadsPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SystemDirectory { path } => {
+                write!(f, "{path} is a system directory and cannot be used for downloads")
+            }
+            Self::HomeDirectory { path } => {
+                write!(f, "{path} is your home folder; pick a subfolder for downloads")
+            }
+        }
+    }
+}
+
+/// Directories a downloads path must never be, since the storage commands
+/// delete things underneath it.
+fn system_dir_blocklist() -> Vec<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        let root = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+        let mut dirs: Vec<PathBuf> = ["Windows", "Program Files", "Program Files (x86)", "ProgramData", "Users"]
+            .iter()
+            .map(|d| PathBuf::from(format!("{root}\\{d}")))
+            .collect();
+        if let Ok(system_root) = std::env::var("SystemRoot") {
+            dirs.push(PathBuf::from(system_root));
+        }
+        dirs
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        [
+            "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib64", "/opt", "/proc",
+            "/root", "/sbin", "/sys", "/tmp", "/usr", "/var", "/Applications", "/Library",
+            "/System", "/Users", "/Volumes", "/private",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+}
+
+/// Canonical, case-folded form for comparing paths on case-insensitive
+/// filesystems (Windows and default macOS volumes).
+fn comparable_path(path: &Path) -> String {
+    let p = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let s = native_path_string(&p);
+    let s = s.trim_end_matches(['/', '\\']);
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        s.to_lowercase()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Resolve a downloads path and refuse filesystem roots, OS directories and
+/// the bare home folder.
+fn checked_downloads_path(downloads_path: &str) -> Result<PathBuf, DownloadsPathError> {
+    let path = resolve_downloads_path(downloads_path);
+    check_not_system_dir(&path)?;
+    Ok(path)
+}
+
+fn check_not_system_dir(path: &Path) -> Result<(), DownloadsPathError> {
+    let display = native_path_string(path);
+    let candidate = comparable_path(path);
+
+    // A root has no parent once canonicalised: `/`, `C:\`, `D:\`.
+    let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if canonical.parent().is_none() || candidate.is_empty() {
+        return Err(DownloadsPathError::SystemDirectory { path: display });
+    }
+    if system_dir_blocklist().iter().any(|d| comparable_path(d) == candidate) {
+        return Err(DownloadsPathError::SystemDirectory { path: display });
+    }
+    if dirs::home_dir().is_some_and(|home| comparable_path(&home) == candidate) {
+        return Err(DownloadsPathError::HomeDirectory { path: display });
+    }
+    Ok(())
+}
+
+/// Suwayomi nests manga under `<downloads>/mangas/<source>/<manga>`; older
+/// layouts put sources straight under the downloads root.
+fn manga_root(downloads: &Path) -> PathBuf {
+    let nested = downloads.join("mangas");
+    if nested.is_dir() { nested } else { downloads.to_path_buf() }
+}
+
+fn unix_secs(t: std::time::SystemTime) -> u64 {
+    t.duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[derive(Serialize)]
+pub struct MangaStorage {
+    source:             String,
+    name:               String,
+    path:               String,
+    bytes:              u64,
+    /// Newest mtime among the folder's files, for "recently downloaded" sorts.
+    last_modified_unix: u64,
+}
+
+/// Per-manga disk usage and freshness, gathered in one walk of the
+/// `<source>/<manga>/...` tree.
 #[tauri::command]
-fn get_storage_info(downloads_path: String) -> Result<StorageInfo, String> {
-    let path = resolve_downloads_path(&downloads_path);
+async fn get_manga_storage(downloads_path: String) -> Result<Vec<MangaStorage>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&resolve_downloads_path(&downloads_path));
+        let mut by_manga: std::collections::HashMap<(String, String), (u64, u64)> =
+            std::collections::HashMap::new();
+        for entry in WalkDir::new(&root).min_depth(3).into_iter().filter_map(|e| e.ok()) {
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+            let Ok(rel) = entry.path().strip_prefix(&root) else { continue };
+            let mut parts = rel.components().map(|c| c.as_os_str().to_string_lossy().into_owned());
+            let (Some(source), Some(manga)) = (parts.next(), parts.next()) else { continue };
+            let slot = by_manga.entry((source, manga)).or_default();
+            slot.0 += meta.len();
+            slot.1 = slot.1.max(meta.modified().map_or(0, unix_secs));
+        }
+        let mut out: Vec<MangaStorage> = by_manga
+            .into_iter()
+            .map(|((source, name), (bytes, last_modified_unix))| MangaStorage {
+                path: native_path_string(&root.join(&source).join(&name)),
+                source,
+                name,
+                bytes,
+                last_modified_unix,
+            })
+            .collect();
+        out.sort_by_key(|m| std::cmp::Reverse(m.bytes));
+        out
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+pub struct StorageEntry {
+    name:       String,
+    path:       String,
+    bytes:      u64,
+    file_count: usize,
+}
 
-    let manga_bytes = if path.exists() {
-        WalkDir::new(&path)
+/// Disk usage of every folder `depth` levels below the manga root, largest
+/// first: 1 for sources, 2 for manga, 3 for chapters. Files sitting above
+/// that level aren't attributed to any entry. Symlinks are never followed,
+/// so a link back up the tree can't loop the walk.
+#[tauri::command]
+async fn get_storage_breakdown(downloads_path: String, depth: u32) -> Result<Vec<StorageEntry>, String> {
+    if depth == 0 {
+        return Err("Depth must be at least 1".to_string());
+    }
+    let depth = depth as usize;
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&resolve_downloads_path(&downloads_path));
+        let mut by_folder: std::collections::HashMap<PathBuf, (u64, usize)> = std::collections::HashMap::new();
+        for entry in WalkDir::new(&root)
+            .follow_links(false)
+            .min_depth(depth + 1)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter_map(|e| e.metadata().ok())
-            .filter(|m| m.is_file())
-            .map(|m| m.len())
-            .sum()
-    } else {
-        0
-    };
+        {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            let Some(folder) = entry.path().ancestors().nth(entry.depth() - depth) else { continue };
+            let slot = by_folder.entry(folder.to_path_buf()).or_default();
+            slot.0 += meta.len();
+            slot.1 += 1;
+        }
+        let mut out: Vec<StorageEntry> = by_folder
+            .into_iter()
+            .map(|(folder, (bytes, file_count))| StorageEntry {
+                name: folder.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                path: native_path_string(&folder),
+                bytes,
+                file_count,
+            })
+            .collect();
+        out.sort_by_key(|e| std::cmp::Reverse(e.bytes));
+        out
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
 
-    let stat_path = if path.exists() { path.clone() } else {
-        dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"))
+#[derive(Serialize)]
+pub struct SourceStorage {
+    source:      String,
+    bytes:       u64,
+    manga_count: usize,
+}
+
+/// Disk usage grouped by source, largest first. `depth` is how many folder
+/// levels sit above the source folders (0 for the usual `<source>/<manga>`
+/// layout), for libraries that add an extra grouping level.
+#[tauri::command]
+async fn get_source_storage(
+    downloads_path: String,
+    depth: Option<usize>,
+) -> Result<Vec<SourceStorage>, String> {
+    let depth = depth.unwrap_or(0);
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&resolve_downloads_path(&downloads_path));
+        let mut by_source: std::collections::HashMap<String, (u64, std::collections::HashSet<String>)> =
+            std::collections::HashMap::new();
+        for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+            let Ok(rel) = entry.path().strip_prefix(&root) else { continue };
+            let parts: Vec<String> = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            // Need at least source/manga/file below the skipped levels.
+            if parts.len() < depth + 3 {
+                continue;
+            }
+            let slot = by_source.entry(parts[depth].clone()).or_default();
+            slot.0 += meta.len();
+            slot.1.insert(parts[depth + 1].clone());
+        }
+        let mut out: Vec<SourceStorage> = by_source
+            .into_iter()
+            .map(|(source, (bytes, manga))| SourceStorage { source, bytes, manga_count: manga.len() })
+            .collect();
+        out.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+        out
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Assumed chapter size when the library has nothing to average over.
+const DEFAULT_CHAPTER_BYTES: u64 = 15 * 1024 * 1024;
+
+#[derive(Serialize)]
+pub struct DownloadSpaceEstimate {
+    estimated_bytes:   u64,
+    free_bytes:        u64,
+    fits:              bool,
+    avg_chapter_bytes: u64,
+}
+
+/// Mean size of a downloaded chapter (a folder or a `.cbz` three levels
+/// below the manga root), or None if nothing is downloaded yet.
+fn average_chapter_bytes(root: &Path) -> Option<u64> {
+    let mut chapters = 0u64;
+    let mut bytes = 0u64;
+    for entry in WalkDir::new(root).min_depth(3).into_iter().filter_map(|e| e.ok()) {
+        if entry.depth() == 3 {
+            chapters += 1;
+        }
+        if entry.file_type().is_file() {
+            bytes += entry.metadata().map_or(0, |m| m.len());
+        }
+    }
+    (chapters > 0).then(|| bytes / chapters)
+}
+
+/// Estimate whether `chapter_count` more chapters fit on the downloads disk,
+/// using the caller's average or one measured from existing downloads.
+#[tauri::command]
+async fn estimate_download_space(
+    downloads_path: String,
+    chapter_count: usize,
+    avg_chapter_bytes: Option<u64>,
+) -> Result<DownloadSpaceEstimate, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = resolve_downloads_path(&downloads_path);
+        let avg = avg_chapter_bytes
+            .filter(|&b| b > 0)
+            .or_else(|| average_chapter_bytes(&manga_root(&path)))
+            .unwrap_or(DEFAULT_CHAPTER_BYTES);
+        let (_, free_bytes) = disk_space_for(&path)?;
+        let estimated_bytes = avg.saturating_mul(chapter_count as u64);
+        Ok(DownloadSpaceEstimate {
+            estimated_bytes,
+            free_bytes,
+            fits: estimated_bytes < free_bytes,
+            avg_chapter_bytes: avg,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "avif", "jxl", "bmp"];
+
+fn is_image_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Order names the way a reader would: `2.jpg` before `10.jpg`.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut n = String::new();
+                    while let Some(c) = it.next_if(char::is_ascii_digit) {
+                        n.push(c);
+                    }
+                    n
+                };
+                let (na, nb) = (take(&mut a), take(&mut b));
+                let (ta, tb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+                let ord = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+                if ord != std::cmp::Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase());
+                if ord != std::cmp::Ordering::Equal {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CbzCheck {
+    ok:          bool,
+    pages:       usize,
+    /// Entries whose data failed to decompress or didn't match its CRC.
+    bad_entries: Vec<String>,
+    /// Set when the archive itself couldn't be opened.
+    error:       Option<String>,
+}
+
+fn check_cbz(path: &Path) -> CbzCheck {
+    let opened = std::fs::File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|f| zip::ZipArchive::new(f).map_err(|e| e.to_string()));
+    let mut archive = match opened {
+        Ok(a) => a,
+        Err(e) => return CbzCheck { ok: false, pages: 0, bad_entries: vec![], error: Some(e) },
     };
+    let mut pages = 0;
+    let mut bad_entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = match archive.by_index(i) {
+            Ok(e) => e,
+            Err(e) => {
+                bad_entries.push(format!("#{i}: {e}"));
+                continue;
+            }
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        // Reading to the end is what makes the zip crate check the CRC.
+        if std::io::copy(&mut entry, &mut std::io::sink()).is_err() {
+            bad_entries.push(name);
+        } else if is_image_name(&name) {
+            pages += 1;
+        }
+    }
+    CbzCheck { ok: bad_entries.is_empty() && pages > 0, pages, bad_entries, error: None }
+}
 
-    let disks = Disks::new_with_refreshed_list();
-    let disk = disks
-        .iter()
-        .filter(|d| stat_path.starts_with(d.mount_point()))
-        .max_by_key(|d| d.mount_point().as_os_str().len())
-        .ok_or_else(|| "Could not find disk for path".to_string())?;
+/// Read every entry of a CBZ to confirm it decompresses cleanly.
+#[tauri::command]
+async fn verify_cbz(path: String) -> Result<CbzCheck, String> {
+    tauri::async_runtime::spawn_blocking(move || check_cbz(Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Cancel flag for a running `verify_library`.
+#[derive(Default)]
+struct VerifyLibraryState {
+    cancel: AtomicBool,
+}
 
-    let total_bytes = disk.total_space();
-    let free_bytes  = disk.available_space();
+#[derive(Serialize, Clone)]
+struct VerifyProgress {
+    current: String,
+    done:    usize,
+    total:   usize,
+}
 
-    Ok(StorageInfo {
-        manga_bytes,
-        total_bytes,
-        free_bytes,
-        path: path.to_string_lossy().into_owned(),
+#[derive(Serialize)]
+pub struct CorruptCbz {
+    path:        String,
+    bad_entries: Vec<String>,
+    error:       Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LibraryVerification {
+    checked:   usize,
+    corrupt:   Vec<CorruptCbz>,
+    cancelled: bool,
+}
+
+/// Verify every CBZ in the library, emitting `verify-progress` after each
+/// file. Stops early, returning what was found so far, once
+/// `cancel_verify_library` is called.
+#[tauri::command]
+async fn verify_library(app: tauri::AppHandle, downloads_path: String) -> Result<LibraryVerification, String> {
+    app.state::<VerifyLibraryState>().cancel.store(false, Ordering::SeqCst);
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&resolve_downloads_path(&downloads_path));
+        let archives: Vec<PathBuf> = WalkDir::new(&root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.file_name().to_string_lossy().to_ascii_lowercase().ends_with(".cbz"))
+            .map(|e| e.into_path())
+            .collect();
+
+        let state = app.state::<VerifyLibraryState>();
+        let total = archives.len();
+        let mut result = LibraryVerification { checked: 0, corrupt: vec![], cancelled: false };
+        for archive in archives {
+            if state.cancel.load(Ordering::SeqCst) {
+                result.cancelled = true;
+                break;
+            }
+            let check = check_cbz(&archive);
+            result.checked += 1;
+            let current = native_path_string(&archive);
+            let _ = app.emit("verify-progress", VerifyProgress { current: current.clone(), done: result.checked, total });
+            if !check.ok {
+                result.corrupt.push(CorruptCbz { path: current, bad_entries: check.bad_entries, error: check.error });
+            }
+        }
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn cancel_verify_library(state: tauri::State<VerifyLibraryState>) {
+    state.cancel.store(true, Ordering::SeqCst);
+}
+
+#[derive(Serialize)]
+pub struct CbzRepair {
+    /// `"intact"`, `"repaired"` or `"unrecoverable"`.
+    status:      &'static str,
+    recovered:   usize,
+    dropped:     usize,
+    backup_path: Option<String>,
+    message:     String,
+}
+
+/// Collect every entry that still reads back cleanly. Falls back to walking
+/// local file headers from the start when a truncated download has lost the
+/// central directory at the end of the file.
+fn salvage_zip_entries(path: &Path) -> (Vec<(String, Vec<u8>)>, usize) {
+    use std::io::Read;
+    let Ok(file) = std::fs::File::open(path) else { return (vec![], 0) };
+
+    // Central directory intact: only individual entries are bad, so skip
+    // those and keep everything else.
+    if let Ok(mut archive) = zip::ZipArchive::new(&file) {
+        let mut good = Vec::new();
+        let mut failed = 0;
+        for i in 0..archive.len() {
+            let Ok(mut entry) = archive.by_index(i) else {
+                failed += 1;
+                continue;
+            };
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            let mut data = Vec::new();
+            match entry.read_to_end(&mut data) {
+                Ok(_) => good.push((name, data)),
+                Err(_) => failed += 1,
+            }
+        }
+        return (good, failed);
+    }
+
+    let Ok(file) = std::fs::File::open(path) else { return (vec![], 0) };
+    let mut reader = std::io::BufReader::new(file);
+    let mut good = Vec::new();
+    let mut failed = 0;
+    loop {
+        match zip::read::read_zipfile_from_stream(&mut reader) {
+            Ok(Some(mut entry)) => {
+                if entry.is_dir() {
+                    continue;
+                }
+                let name = entry.name().to_string();
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                match entry.read_to_end(&mut data) {
+                    Ok(_) => good.push((name, data)),
+                    // A torn entry leaves the stream mid-record; nothing after
+                    // it can be trusted.
+                    Err(_) => {
+                        failed += 1;
+                        break;
+                    }
+                }
+            }
+            Ok(None) => break,
+            Err(_) => {
+                failed += 1;
+                break;
+            }
+        }
+    }
+    (good, failed)
+}
+
+/// Rebuild a damaged CBZ from the entries that are still readable, backing
+/// the original up to `<file>.bak.<unix time>` first.
+#[tauri::command]
+async fn repair_cbz(path: String) -> Result<CbzRepair, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        use std::io::Write;
+
+        let path = PathBuf::from(path);
+        let check = check_cbz(&path);
+        if check.ok {
+            return Ok(CbzRepair {
+                status: "intact",
+                recovered: check.pages,
+                dropped: 0,
+                backup_path: None,
+                message: "Archive is intact; nothing to repair".to_string(),
+            });
+        }
+
+        let (entries, failed) = salvage_zip_entries(&path);
+        let pages: Vec<&(String, Vec<u8>)> = entries.iter().filter(|(n, _)| is_image_name(n)).collect();
+        // With a readable central directory we know how many pages there
+        // should have been; otherwise count the entry we choked on.
+        let expected = if check.error.is_none() {
+            check.pages + check.bad_entries.len()
+        } else {
+            pages.len() + failed
+        };
+        let dropped = expected.saturating_sub(pages.len());
+        if pages.is_empty() {
+            return Ok(CbzRepair {
+                status: "unrecoverable",
+                recovered: 0,
+                dropped,
+                backup_path: None,
+                message: "Archive is beyond repair; re-download this chapter".to_string(),
+            });
+        }
+
+        // Earlier backups may be the only good copy left, so each repair
+        // gets its own timestamped name rather than reusing `.bak`.
+        let stamp = unix_secs(std::time::SystemTime::now());
+        let backup = (0..)
+            .map(|n| {
+                let mut name = path.as_os_str().to_owned();
+                name.push(format!(".bak.{stamp}"));
+                if n > 0 {
+                    name.push(format!("-{n}"));
+                }
+                PathBuf::from(name)
+            })
+            .find(|p| !p.exists())
+            .expect("unbounded range always yields a free name");
+        std::fs::copy(&path, &backup).map_err(|e| format!("Could not back up original: {e}"))?;
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".repair.tmp");
+        let tmp = PathBuf::from(tmp);
+        let write = || -> zip::result::ZipResult<()> {
+            let mut out = zip::ZipWriter::new(std::fs::File::create(&tmp)?);
+            let opts = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored);
+            for (name, data) in &entries {
+                out.start_file(name.as_str(), opts)?;
+                out.write_all(data)?;
+            }
+            out.finish()?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(format!("Could not write repaired archive: {e}"));
+        }
+        std::fs::rename(&tmp, &path).map_err(|e| format!("Could not replace archive: {e}"))?;
+
+        println!("Repaired {path:?}: {} pages kept, {dropped} dropped", pages.len());
+        Ok(CbzRepair {
+            status: "repaired",
+            recovered: pages.len(),
+            dropped,
+            backup_path: Some(native_path_string(&backup)),
+            message: format!("Recovered {} pages, dropped {dropped}", pages.len()),
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Unpack a CBZ into a folder of loose images in reading order. Pages keep
+/// their names when those already sort correctly and are renamed to
+/// zero-padded sequence numbers otherwise. Returns the page count.
+#[tauri::command]
+async fn extract_cbz(archive: String, output_dir: String, force: bool) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let check = check_cbz(Path::new(&archive));
+        if !check.ok {
+            return Err(match check.error {
+                Some(e) => format!("Not a readable CBZ: {e}"),
+                None if check.pages == 0 => "Archive contains no images".to_string(),
+                None => format!("Archive is damaged: {}", check.bad_entries.join(", ")),
+            });
+        }
+
+        let out = expand_user_path(&output_dir);
+        let non_empty = std::fs::read_dir(&out).is_ok_and(|mut d| d.next().is_some());
+        if non_empty && !force {
+            return Err(format!("{} is not empty", out.display()));
+        }
+        std::fs::create_dir_all(&out).map_err(|e| format!("Could not create {}: {e}", out.display()))?;
+
+        let file = std::fs::File::open(&archive).map_err(|e| e.to_string())?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+        let mut pages: Vec<(usize, String)> = (0..zip.len())
+            .filter_map(|i| {
+                let entry = zip.by_index(i).ok()?;
+                let name = entry.enclosed_name()?.to_string_lossy().into_owned();
+                (!entry.is_dir() && is_image_name(&name)).then_some((i, name))
+            })
+            .collect();
+        pages.sort_by(|a, b| natural_cmp(&a.1, &b.1));
+
+        let flat = pages.iter().all(|(_, n)| !n.contains(['/', '\\']));
+        let lexical_ok = pages.windows(2).all(|w| w[0].1 < w[1].1);
+        let rename = !(flat && lexical_ok);
+        let width = pages.len().to_string().len().max(3);
+
+        for (seq, (index, name)) in pages.iter().enumerate() {
+            let file_name = if rename {
+                let ext = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+                format!("{:0width$}.{}", seq + 1, ext.to_ascii_lowercase())
+            } else {
+                name.clone()
+            };
+            let mut entry = zip.by_index(*index).map_err(|e| e.to_string())?;
+            let mut dest = std::fs::File::create(out.join(&file_name))
+                .map_err(|e| format!("Could not write {file_name}: {e}"))?;
+            std::io::copy(&mut entry, &mut dest).map_err(|e| format!("Could not extract {name}: {e}"))?;
+        }
+        Ok(pages.len())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChapterKind {
+    Cbz,
+    Loose,
+}
+
+/// Every chapter found `chapter_depth` levels below `root` (3 for the usual
+/// `<source>/<manga>/<chapter>`), in one walk. A folder only counts as a
+/// loose chapter once an image turns up inside it.
+fn scan_chapters(root: &Path, chapter_depth: usize) -> Vec<(PathBuf, ChapterKind)> {
+    let mut found = Vec::new();
+    let mut pending_dir: Option<PathBuf> = None;
+    for entry in WalkDir::new(root)
+        .min_depth(chapter_depth)
+        .max_depth(chapter_depth + 1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let is_file = entry.file_type().is_file();
+        let name = entry.file_name().to_string_lossy();
+        if entry.depth() == chapter_depth {
+            pending_dir = None;
+            if entry.file_type().is_dir() {
+                pending_dir = Some(entry.path().to_path_buf());
+            } else if is_file && name.to_ascii_lowercase().ends_with(".cbz") {
+                found.push((entry.path().to_path_buf(), ChapterKind::Cbz));
+            }
+        } else if is_file && is_image_name(&name) {
+            if let Some(dir) = pending_dir.take() {
+                found.push((dir, ChapterKind::Loose));
+            }
+        }
+    }
+    found
+}
+
+#[derive(Serialize)]
+pub struct ChapterClassification {
+    cbz_count:   usize,
+    loose_count: usize,
+    /// Loose-image chapter folders still to convert.
+    loose:       Vec<String>,
+}
+
+/// Count CBZ vs loose-image chapters across the library.
+#[tauri::command]
+async fn classify_chapters(downloads_path: String) -> Result<ChapterClassification, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&resolve_downloads_path(&downloads_path));
+        let chapters = scan_chapters(&root, 3);
+        let loose: Vec<String> = chapters
+            .iter()
+            .filter(|(_, k)| *k == ChapterKind::Loose)
+            .map(|(p, _)| native_path_string(p))
+            .collect();
+        ChapterClassification {
+            cbz_count: chapters.len() - loose.len(),
+            loose_count: loose.len(),
+            loose,
+        }
     })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+pub struct LibraryCounts {
+    manga_count:        usize,
+    chapter_count:      usize,
+    cbz_count:          usize,
+    loose_folder_count: usize,
+}
+
+/// Manga and chapter totals from the filesystem alone, with chapters
+/// classified by `scan_chapters`. `chapter_depth` is how far chapters sit
+/// below the manga root: 3 for `<source>/<manga>/<chapter>` (the default),
+/// 2 for `<manga>/<chapter>`.
+#[tauri::command]
+async fn count_library(downloads_path: String, chapter_depth: Option<usize>) -> Result<LibraryCounts, String> {
+    let depth = chapter_depth.unwrap_or(3);
+    if depth < 2 {
+        return Err("Chapter depth must be at least 2".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&resolve_downloads_path(&downloads_path));
+        let manga_count = WalkDir::new(&root)
+            .min_depth(depth - 1)
+            .max_depth(depth - 1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+            .count();
+        let chapters = scan_chapters(&root, depth);
+        let cbz_count = chapters.iter().filter(|(_, kind)| *kind == ChapterKind::Cbz).count();
+        LibraryCounts {
+            manga_count,
+            chapter_count: chapters.len(),
+            cbz_count,
+            loose_folder_count: chapters.len() - cbz_count,
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+pub struct CleanupItem {
+    path:  String,
+    bytes: u64,
+}
+
+/// Shared by every destructive storage command. With `dry_run` the items
+/// are what would be removed and nothing on disk has changed.
+#[derive(Serialize)]
+pub struct CleanupResult {
+    dry_run:     bool,
+    items:       Vec<CleanupItem>,
+    bytes_freed: u64,
+    /// Items that failed to delete; they are left out of `items`.
+    errors:      Vec<String>,
+}
+
+fn path_bytes(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Delete (or, on a dry run, just measure) each target, to the OS trash
+/// when `to_trash` is set.
+fn remove_cleanup_targets(targets: Vec<PathBuf>, dry_run: bool, to_trash: bool) -> CleanupResult {
+    let mut result = CleanupResult { dry_run, items: vec![], bytes_freed: 0, errors: vec![] };
+    for target in targets {
+        let bytes = path_bytes(&target);
+        if !dry_run {
+            let removed = if to_trash {
+                trash::delete(&target).map_err(|e| e.to_string())
+            } else if target.is_dir() {
+                std::fs::remove_dir_all(&target).map_err(|e| e.to_string())
+            } else {
+                std::fs::remove_file(&target).map_err(|e| e.to_string())
+            };
+            if let Err(e) = removed {
+                result.errors.push(format!("{}: {e}", target.display()));
+                continue;
+            }
+        }
+        result.bytes_freed += bytes;
+        result.items.push(CleanupItem { path: native_path_string(&target), bytes });
+    }
+    result
+}
+
+/// Leftovers of interrupted downloads: `.tmp`/`.part` files, and source,
+/// manga or chapter folders that contain no files at all.
+#[tauri::command]
+async fn clean_orphaned_downloads(downloads_path: String, dry_run: bool) -> Result<CleanupResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&checked_downloads_path(&downloads_path).map_err(|e| e.to_string())?);
+        let mut targets: Vec<PathBuf> = Vec::new();
+        let mut walker = WalkDir::new(&root).min_depth(1).sort_by_file_name().into_iter();
+        while let Some(entry) = walker.next() {
+            let Ok(entry) = entry else { continue };
+            let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+            if entry.file_type().is_dir() && entry.depth() <= 3 {
+                let has_files = WalkDir::new(entry.path())
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .any(|e| e.file_type().is_file());
+                if !has_files {
+                    targets.push(entry.path().to_path_buf());
+                    walker.skip_current_dir();
+                }
+            } else if entry.file_type().is_file() && (name.ends_with(".tmp") || name.ends_with(".part")) {
+                targets.push(entry.path().to_path_buf());
+            }
+        }
+        Ok(remove_cleanup_targets(targets, dry_run, false))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Resolve `folder` and require it to be a manga folder: a directory exactly
+/// `<source>/<manga>` below the library's manga root (`root`, as given by
+/// `comparable_path`). Symlinks are resolved first, so one pointing out of
+/// the library is refused.
+fn checked_manga_folder(root: &str, folder: &str) -> Result<PathBuf, String> {
+    let path = resolve_downloads_path(folder);
+    let depth = comparable_path(&path)
+        .strip_prefix(root)
+        .filter(|rel| rel.starts_with(['/', '\\']))
+        .map_or(0, |rel| rel.split(['/', '\\']).filter(|c| !c.is_empty()).count());
+    if depth != 2 || !path.is_dir() {
+        return Err(format!("{folder} is not a manga folder inside the library"));
+    }
+    Ok(path)
+}
+
+/// Delete whole manga folders (`<source>/<manga>`), permanently unless
+/// `permanent` is false, in which case they go to the OS trash. Every path
+/// is checked to be a manga folder inside the library before anything is
+/// removed.
+#[tauri::command]
+async fn delete_manga_folders(
+    downloads_path: String,
+    folders: Vec<String>,
+    dry_run: bool,
+    permanent: Option<bool>,
+) -> Result<CleanupResult, String> {
+    let to_trash = !permanent.unwrap_or(true);
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&checked_downloads_path(&downloads_path).map_err(|e| e.to_string())?);
+        let root = comparable_path(&root);
+        let targets = folders
+            .iter()
+            .map(|folder| checked_manga_folder(&root, folder))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(remove_cleanup_targets(targets, dry_run, to_trash))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Delete one manga folder, to the OS trash unless `permanent` is set, and
+/// return the bytes reclaimed. Same checks as `delete_manga_folders`.
+#[tauri::command]
+async fn delete_manga_folder(downloads_path: String, path: String, permanent: bool) -> Result<u64, String> {
+    let result = delete_manga_folders(downloads_path, vec![path], false, Some(permanent)).await?;
+    if !result.errors.is_empty() {
+        return Err(result.errors.join("; "));
+    }
+    Ok(result.bytes_freed)
+}
+
+/// Remove loose-image chapter folders that also exist as a readable
+/// `<chapter>.cbz` next to them, keeping the archive.
+#[tauri::command]
+async fn remove_duplicate_chapters(downloads_path: String, dry_run: bool) -> Result<CleanupResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = manga_root(&checked_downloads_path(&downloads_path).map_err(|e| e.to_string())?);
+        let targets: Vec<PathBuf> = scan_chapters(&root, 3)
+            .into_iter()
+            .filter(|(_, kind)| *kind == ChapterKind::Loose)
+            .map(|(dir, _)| dir)
+            .filter(|dir| {
+                let mut cbz = dir.as_os_str().to_owned();
+                cbz.push(".cbz");
+                let cbz = PathBuf::from(cbz);
+                cbz.is_file() && check_cbz(&cbz).ok
+            })
+            .collect();
+        Ok(remove_cleanup_targets(targets, dry_run, false))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Settings for the background storage rescan that runs while the user is away.
+struct IdleScanState {
+    enabled:        AtomicBool,
+    threshold_secs: AtomicU64,
+    downloads_path: Mutex<String>,
+}
+
+impl Default for IdleScanState {
+    fn default() -> Self {
+        Self {
+            enabled:        AtomicBool::new(false),
+            threshold_secs: AtomicU64::new(300),
+            downloads_path: Mutex::new(String::new()),
+        }
+    }
+}
+
+/// How often the scheduler wakes to check idleness, and the minimum gap
+/// between two idle scans.
+const IDLE_POLL_SECS: u64 = 60;
+const IDLE_RESCAN_SECS: u64 = 15 * 60;
+
+/// Seconds since the last keyboard/mouse input, or None when the platform
+/// won't say (in which case we never treat the machine as idle).
+#[cfg(target_os = "windows")]
+fn system_idle_secs() -> Option<u64> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    // SAFETY: `info` is a valid, correctly sized LASTINPUTINFO.
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // SAFETY: GetTickCount has no preconditions.
+    let now = unsafe { GetTickCount() };
+    Some(u64::from(now.wrapping_sub(info.dwTime)) / 1000)
+}
+
+#[cfg(target_os = "macos")]
+fn system_idle_secs() -> Option<u64> {
+    let out = std::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let line = text.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+    let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(nanos / 1_000_000_000)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn system_idle_secs() -> Option<u64> {
+    // X11 sessions usually have xprintidle; GNOME on Wayland exposes Mutter's
+    // idle monitor over D-Bus instead.
+    if let Ok(out) = std::process::Command::new("xprintidle").output() {
+        if let Ok(ms) = String::from_utf8_lossy(&out.stdout).trim().parse::<u64>() {
+            return Some(ms / 1000);
+        }
+    }
+    let out = std::process::Command::new("gdbus")
+        .args([
+            "call", "--session",
+            "--dest", "org.gnome.Mutter.IdleMonitor",
+            "--object-path", "/org/gnome/Mutter/IdleMonitor/Core",
+            "--method", "org.gnome.Mutter.IdleMonitor.GetIdletime",
+        ])
+        .output()
+        .ok()?;
+    // Reply looks like `(uint64 12345,)`.
+    let text = String::from_utf8_lossy(&out.stdout);
+    let ms: u64 = text
+        .trim()
+        .trim_start_matches("(uint64 ")
+        .trim_end_matches(",)")
+        .parse()
+        .ok()?;
+    Some(ms / 1000)
+}
+
+/// Whether the server's downloader is started; `None` when the server
+/// can't be asked.
+async fn downloader_running() -> Option<bool> {
+    local_graphql("{ downloadStatus { state } }")
+        .await
+        .ok()
+        .and_then(|d| d.pointer("/downloadStatus/state").and_then(|v| v.as_str()).map(|s| s == "STARTED"))
+}
+
+/// Whether the server's downloader is busy, so an idle scan doesn't compete
+/// with it for disk IO. An unreachable server counts as idle.
+async fn server_downloads_active() -> bool {
+    let Ok(data) = local_graphql("{ downloadStatus { state queue { state } } }").await else {
+        return false;
+    };
+    let started = data.pointer("/downloadStatus/state").and_then(|v| v.as_str()) == Some("STARTED");
+    let queued = data
+        .pointer("/downloadStatus/queue")
+        .and_then(|v| v.as_array())
+        .is_some_and(|q| !q.is_empty());
+    started && queued
+}
+
+/// Background loop started from `setup`: when enabled and the machine has
+/// been idle past the threshold, rescan storage and emit `storage-updated`.
+fn start_idle_scan_scheduler(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_scan: Option<std::time::Instant> = None;
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(IDLE_POLL_SECS)).await;
+            let state = app.state::<IdleScanState>();
+            if !state.enabled.load(Ordering::SeqCst) {
+                continue;
+            }
+            if last_scan.is_some_and(|t| t.elapsed().as_secs() < IDLE_RESCAN_SECS) {
+                continue;
+            }
+            let threshold = state.threshold_secs.load(Ordering::SeqCst);
+            if system_idle_secs().is_none_or(|idle| idle < threshold) {
+                continue;
+            }
+            if server_downloads_active().await {
+                continue;
+            }
+            let downloads_path = lock_unpoisoned(&state.downloads_path).clone();
+            let scan = tauri::async_runtime::spawn_blocking(move || storage_info_blocking(&downloads_path)).await;
+            last_scan = Some(std::time::Instant::now());
+            if let Ok(Ok(info)) = scan {
+                let _ = app.emit("storage-updated", info);
+            }
+        }
+    });
+}
+
+/// Turn the idle-time storage rescan on or off for `downloads_path`.
+#[tauri::command]
+fn set_idle_scan(enabled: bool, downloads_path: String, state: tauri::State<IdleScanState>) {
+    *lock_unpoisoned(&state.downloads_path) = downloads_path;
+    state.enabled.store(enabled, Ordering::SeqCst);
+}
+
+/// How long the machine must be idle before a rescan, clamped to at least
+/// one minute.
+#[tauri::command]
+fn set_idle_scan_threshold(secs: u64, state: tauri::State<IdleScanState>) -> u64 {
+    let secs = secs.max(60);
+    state.threshold_secs.store(secs, Ordering::SeqCst);
+    secs
+}
+
+/// Subnets phones hand out when tethering: iPhone Personal Hotspot, and
+/// Android USB / Wi-Fi hotspot.
+#[cfg(not(target_os = "windows"))]
+fn on_tethered_subnet() -> bool {
+    if_addrs::get_if_addrs().unwrap_or_default().iter().any(|iface| match iface.ip() {
+        std::net::IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            (a, b, c, d & 0xF0) == (172, 20, 10, 0) || (a, b) == (192, 168) && matches!(c, 42 | 43)
+        }
+        std::net::IpAddr::V6(_) => false,
+    })
+}
+
+/// Whether the active connection is metered, or None if we can't tell.
+#[cfg(target_os = "windows")]
+fn detect_metered_connection() -> Option<bool> {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+    let profile = NetworkInformation::GetInternetConnectionProfile().ok()?;
+    let cost = profile.GetConnectionCost().ok()?;
+    let kind = cost.NetworkCostType().ok()?;
+    Some(
+        kind == NetworkCostType::Fixed
+            || kind == NetworkCostType::Variable
+            || cost.Roaming().unwrap_or(false)
+            || cost.OverDataLimit().unwrap_or(false),
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn detect_metered_connection() -> Option<bool> {
+    // macOS only exposes "expensive" paths to Network.framework, so fall back
+    // to recognising a phone's tethering subnet.
+    on_tethered_subnet().then_some(true)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn detect_metered_connection() -> Option<bool> {
+    // NetworkManager's global Metered property: 1 = yes, 3 = guess-yes,
+    // 2 / 4 = no / guess-no, 0 = unknown. Reply looks like `(<uint32 4>,)`.
+    let nm = std::process::Command::new("gdbus")
+        .args([
+            "call", "--system",
+            "--dest", "org.freedesktop.NetworkManager",
+            "--object-path", "/org/freedesktop/NetworkManager",
+            "--method", "org.freedesktop.DBus.Properties.Get",
+            "org.freedesktop.NetworkManager", "Metered",
+        ])
+        .output()
+        .ok()
+        .and_then(|out| {
+            let text = String::from_utf8_lossy(&out.stdout).into_owned();
+            text.trim()
+                .trim_start_matches("(<uint32 ")
+                .trim_end_matches(">,)")
+                .parse::<u32>()
+                .ok()
+        });
+    match nm {
+        Some(1 | 3) => Some(true),
+        Some(2 | 4) => Some(on_tethered_subnet()),
+        _ => on_tethered_subnet().then_some(true),
+    }
+}
+
+/// Whether the current connection is metered; null when unknown.
+#[tauri::command]
+async fn is_connection_metered() -> Option<bool> {
+    tauri::async_runtime::spawn_blocking(detect_metered_connection)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Opt-in: pause the server's downloader while on a metered connection.
+#[derive(Default)]
+struct MeteredPauseState {
+    enabled:      AtomicBool,
+    /// Set only when *we* paused, so we never resume a user's manual pause.
+    paused_by_us: AtomicBool,
+}
+
+const METERED_POLL_SECS: u64 = 30;
+
+/// Background loop started from `setup` that pauses downloads on metered
+/// connections and resumes them once back on an unmetered one.
+fn start_metered_watch(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(METERED_POLL_SECS)).await;
+            let state = app.state::<MeteredPauseState>();
+            if !state.enabled.load(Ordering::SeqCst) {
+                continue;
+            }
+            let metered = tauri::async_runtime::spawn_blocking(detect_metered_connection)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(false);
+            let paused = state.paused_by_us.load(Ordering::SeqCst);
+            if metered && !paused {
+                // Already stopped means the user paused; leave that pause to them.
+                if downloader_running().await == Some(true)
+                    && local_graphql("mutation { stopDownloader(input: {}) { clientMutationId } }").await.is_ok()
+                {
+                    state.paused_by_us.store(true, Ordering::SeqCst);
+                    println!("Metered connection detected, paused downloads");
+                    let _ = app.emit("downloads-paused-metered", ());
+                }
+            } else if !metered && paused
+                && local_graphql("mutation { startDownloader(input: {}) { clientMutationId } }").await.is_ok()
+            {
+                state.paused_by_us.store(false, Ordering::SeqCst);
+                println!("Back on an unmetered connection, resumed downloads");
+                let _ = app.emit("downloads-resumed-metered", ());
+            }
+        }
+    });
+}
+
+/// Enable or disable pausing downloads on metered connections.
+#[tauri::command]
+fn set_pause_on_metered(enabled: bool, state: tauri::State<MeteredPauseState>) {
+    state.enabled.store(enabled, Ordering::SeqCst);
+    if !enabled {
+        state.paused_by_us.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Check a downloads path before saving it in Settings.
+#[tauri::command]
+fn validate_downloads_path(downloads_path: String) -> Result<String, DownloadsPathError> {
+    checked_downloads_path(&downloads_path).map(|p| native_path_string(&p))
+}
+
+/// (total, available) bytes of the disk holding `path`, picking the longest
+/// matching mount point so separate mounts report their own numbers.
+fn disk_space_for(path: &Path) -> Result<(u64, u64), String> {
+    let stat_path = if path.exists() { path.to_path_buf() } else {
+        dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"))
+    };
+
+    let disks = Disks::new_with_refreshed_list();
+    let disk = disks
+        .iter()
+        .filter(|d| stat_path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .ok_or_else(|| "Could not find disk for path".to_string())?;
+
+    Ok((disk.total_space(), disk.available_space()))
+}
+
+const DEFAULT_LOW_DISK_THRESHOLD: u64 = 2_000_000_000;
+const LOW_DISK_POLL_SECS: u64 = 60;
+
+/// Free-space threshold for `low-disk-space`, and whether we've already
+/// warned since space last recovered.
+struct LowDiskState {
+    threshold: AtomicU64,
+    warned:    AtomicBool,
+}
+
+impl Default for LowDiskState {
+    fn default() -> Self {
+        Self {
+            threshold: AtomicU64::new(load_setting("low_disk_threshold").unwrap_or(DEFAULT_LOW_DISK_THRESHOLD)),
+            warned:    AtomicBool::new(false),
+        }
+    }
+}
+
+/// Payload of the `low-disk-space` event.
+#[derive(Serialize, Clone)]
+struct LowDiskSpace {
+    path:            String,
+    free_bytes:      u64,
+    total_bytes:     u64,
+    threshold_bytes: u64,
+}
+
+/// Background loop started from `setup` that emits `low-disk-space` once
+/// when the disk holding the server's downloads drops below the threshold,
+/// and again only after it has recovered and dropped once more.
+fn start_low_disk_watch(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let check = tauri::async_runtime::spawn_blocking(|| {
+                let conf = std::fs::read_to_string(suwayomi_data_dir().join("server.conf")).unwrap_or_default();
+                let path = resolve_downloads_path(&read_conf_value(&conf, "server.downloadsPath").unwrap_or_default());
+                disk_space_for(&path).map(|(total, free)| (path, total, free))
+            });
+            if let Ok(Ok((path, total_bytes, free_bytes))) = check.await {
+                let state = app.state::<LowDiskState>();
+                let threshold_bytes = state.threshold.load(Ordering::SeqCst);
+                if free_bytes >= threshold_bytes {
+                    state.warned.store(false, Ordering::SeqCst);
+                } else if !state.warned.swap(true, Ordering::SeqCst) {
+                    eprintln!("Low disk space: {} MB free for {path:?}", free_bytes / 1_000_000);
+                    let _ = app.emit("low-disk-space", LowDiskSpace {
+                        path: native_path_string(&path),
+                        free_bytes,
+                        total_bytes,
+                        threshold_bytes,
+                    });
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(LOW_DISK_POLL_SECS)).await;
+        }
+    });
+}
+
+/// Change the low-disk warning threshold. The next check warns again if
+/// free space is already below the new value.
+#[tauri::command]
+fn set_low_disk_threshold(bytes: u64, state: tauri::State<LowDiskState>) -> Result<(), String> {
+    store_setting("low_disk_threshold", serde_json::json!(bytes))?;
+    state.threshold.store(bytes, Ordering::SeqCst);
+    state.warned.store(false, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Cancel flag for a running `get_storage_info` walk.
+#[derive(Default)]
+struct StorageScanState {
+    cancel: AtomicBool,
+}
+
+/// Payload of `storage-progress`: the disk stats up front, then a running
+/// `manga_bytes` total while the downloads folder is walked.
+#[derive(Serialize, Clone)]
+struct StorageProgress {
+    manga_bytes: u64,
+    total_bytes: u64,
+    free_bytes:  u64,
+    done:        bool,
+}
+
+/// Sum the size of every file under `path`, calling `progress` with the
+/// running total every so often. Returns `None` once `cancel` is set.
+fn downloads_bytes(path: &Path, cancel: &AtomicBool, mut progress: impl FnMut(u64)) -> Option<u64> {
+    let mut total = 0;
+    let mut last_report = std::time::Instant::now();
+    for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        if let Some(meta) = entry.metadata().ok().filter(|m| m.is_file()) {
+            total += meta.len();
+        }
+        if last_report.elapsed() >= std::time::Duration::from_millis(250) {
+            progress(total);
+            last_report = std::time::Instant::now();
+        }
+    }
+    Some(total)
+}
+
+/// The full storage summary in one blocking call, for callers already off
+/// the main thread.
+fn storage_info_blocking(downloads_path: &str) -> Result<StorageInfo, String> {
+    let path = resolve_downloads_path(downloads_path);
+    let (total_bytes, free_bytes) = disk_space_for(&path)?;
+    let manga_bytes = downloads_bytes(&path, &AtomicBool::new(false), |_| {}).unwrap_or(0);
+    Ok(StorageInfo {
+        manga_bytes,
+        total_bytes,
+        free_bytes,
+        path: path.to_string_lossy().into_owned(),
+    })
+}
+
+/// Disk stats go out at once as a `storage-progress` event; the downloads
+/// walk then runs on a blocking thread, reporting its running total, and
+/// aborts when `cancel_storage_scan` is called.
+#[tauri::command]
+async fn get_storage_info(app: tauri::AppHandle, downloads_path: String) -> Result<StorageInfo, String> {
+    app.state::<StorageScanState>().cancel.store(false, Ordering::SeqCst);
+    let path = resolve_downloads_path(&downloads_path);
+    let (total_bytes, free_bytes) = disk_space_for(&path)?;
+    let progress = move |manga_bytes, done| StorageProgress { manga_bytes, total_bytes, free_bytes, done };
+    let _ = app.emit("storage-progress", progress(0, false));
+
+    let walk_path = path.clone();
+    let walk_app = app.clone();
+    let manga_bytes = tauri::async_runtime::spawn_blocking(move || {
+        let state = walk_app.state::<StorageScanState>();
+        downloads_bytes(&walk_path, &state.cancel, |bytes| {
+            let _ = walk_app.emit("storage-progress", progress(bytes, false));
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Storage scan cancelled")?;
+    let _ = app.emit("storage-progress", progress(manga_bytes, true));
+
+    Ok(StorageInfo {
+        manga_bytes,
+        total_bytes,
+        free_bytes,
+        path: path.to_string_lossy().into_owned(),
+    })
+}
+
+#[tauri::command]
+fn cancel_storage_scan(state: tauri::State<StorageScanState>) {
+    state.cancel.store(true, Ordering::SeqCst);
+}
+
+/// The downloads folder exactly as the backend resolves it, so the frontend
+/// can build manga paths against one source of truth.
+#[tauri::command]
+fn get_downloads_path(downloads_path: String) -> String {
+    native_path_string(&resolve_downloads_path(&downloads_path))
+}
+
+/// Recursively copy a directory tree, creating `to` as needed.
+fn copy_dir_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &dest)?;
+        } else {
+            std::fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move every entry of `from` into `to`. Tries a cheap rename first and
+/// falls back to copy-then-delete when the two sit on different filesystems.
+fn move_dir_contents(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if dest.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists in the target", dest.display()),
+            ));
+        }
+        if std::fs::rename(entry.path(), &dest).is_ok() {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &dest)?;
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::copy(entry.path(), &dest)?;
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn link_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Junctions don't need the elevated privilege that real symlinks do.
+#[cfg(target_os = "windows")]
+fn link_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(target)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("mklink /J exited with {status}")))
+    }
+}
+
+/// Point the default downloads folder at another location (e.g. an external
+/// drive) via a symlink, or a junction on Windows.
+///
+/// An existing real folder must be empty unless `move_existing` is set, in
+/// which case its contents are moved into `target` first.
+#[tauri::command]
+fn link_downloads_to(target: String, move_existing: bool) -> Result<String, String> {
+    let link = resolve_downloads_path("");
+    let target = PathBuf::from(target.trim());
+    if target.as_os_str().is_empty() {
+        return Err("Target path is empty".to_string());
+    }
+    probe_writable(&target).map_err(|e| format!("Target is not writable: {e}"))?;
+    let target = std::fs::canonicalize(&target).map_err(|e| e.to_string())?;
+    check_not_system_dir(&target).map_err(|e| e.to_string())?;
+    if target.starts_with(&link) {
+        return Err("Target must not be inside the downloads folder".to_string());
+    }
+
+    match std::fs::symlink_metadata(&link) {
+        // Already a link — just repoint it.
+        Ok(meta) if meta.file_type().is_symlink() => {
+            std::fs::remove_file(&link)
+                .or_else(|_| std::fs::remove_dir(&link))
+                .map_err(|e| format!("Could not remove existing link: {e}"))?;
+        }
+        Ok(meta) if meta.is_dir() => {
+            let is_empty = std::fs::read_dir(&link)
+                .map_err(|e| e.to_string())?
+                .next()
+                .is_none();
+            if !is_empty {
+                if !move_existing {
+                    return Err(format!(
+                        "{} already contains downloads; move them to the new location first",
+                        link.display()
+                    ));
+                }
+                move_dir_contents(&link, &target)
+                    .map_err(|e| format!("Could not move existing downloads: {e}"))?;
+            }
+            std::fs::remove_dir(&link)
+                .map_err(|e| format!("Could not remove old downloads folder: {e}"))?;
+        }
+        Ok(_) => return Err(format!("{} exists and is not a folder", link.display())),
+        Err(_) => {
+            if let Some(parent) = link.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    link_dir(&target, &link).map_err(|e| format!("Could not create link: {e}"))?;
+    println!("Linked {link:?} -> {target:?}");
+    Ok(native_path_string(&target))
+}
+
+type DownloadsDebouncer = notify_debouncer_full::Debouncer<
+    notify_debouncer_full::notify::RecommendedWatcher,
+    notify_debouncer_full::RecommendedCache,
+>;
+
+/// The active downloads watcher; dropping it stops watching.
+struct DownloadsWatcher(Mutex<Option<DownloadsDebouncer>>);
+
+#[derive(Serialize, Clone)]
+struct DownloadsChanged {
+    path: String,
+    /// `"created"`, `"removed"` or `"modified"`.
+    kind: &'static str,
+}
+
+/// Watch the downloads folder and emit debounced `downloads-changed`
+/// events, replacing any previous watch.
+#[tauri::command]
+fn watch_downloads(
+    downloads_path: String,
+    app: tauri::AppHandle,
+    state: tauri::State<DownloadsWatcher>,
+) -> Result<String, String> {
+    use notify_debouncer_full::notify::{EventKind, RecursiveMode};
+
+    let path = resolve_downloads_path(&downloads_path);
+    std::fs::create_dir_all(&path).map_err(|e| format!("Could not create {}: {e}", path.display()))?;
+
+    let handle = app.clone();
+    let mut debouncer = notify_debouncer_full::new_debouncer(
+        std::time::Duration::from_millis(500),
+        None,
+        move |result: notify_debouncer_full::DebounceEventResult| {
+            let Ok(events) = result else { return };
+            for event in events {
+                let kind = match event.kind {
+                    EventKind::Create(_) => "created",
+                    EventKind::Remove(_) => "removed",
+                    EventKind::Modify(_) => "modified",
+                    _ => continue,
+                };
+                for p in &event.paths {
+                    let _ = handle.emit("downloads-changed", DownloadsChanged {
+                        path: p.to_string_lossy().into_owned(),
+                        kind,
+                    });
+                }
+            }
+        },
+    )
+    .map_err(|e| format!("Could not start watcher: {e}"))?;
+    debouncer
+        .watch(&path, RecursiveMode::Recursive)
+        .map_err(|e| format!("Could not watch {}: {e}", path.display()))?;
+
+    *lock_unpoisoned(&state.0) = Some(debouncer);
+    Ok(native_path_string(&path))
+}
+
+/// Stop watching the downloads folder. Returns false if nothing was watched.
+#[tauri::command]
+fn unwatch_downloads(state: tauri::State<DownloadsWatcher>) -> bool {
+    lock_unpoisoned(&state.0).take().is_some()
+}
+
+/// Per-directory results of `path_is_case_sensitive`; a volume's behaviour
+/// doesn't change while we run.
+static CASE_SENSITIVITY: Mutex<Option<std::collections::HashMap<PathBuf, bool>>> = Mutex::new(None);
+
+/// Probe by creating two files whose names differ only in case: on a
+/// case-insensitive volume the second create collides with the first.
+fn probe_case_sensitive(dir: &Path) -> std::io::Result<bool> {
+    let stem = format!(".moku-case-probe-{}", std::process::id());
+    let lower = dir.join(format!("{stem}-a"));
+    let upper = dir.join(format!("{stem}-A"));
+    std::fs::File::create(&lower)?;
+    let result = match std::fs::OpenOptions::new().write(true).create_new(true).open(&upper) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_file(&upper);
+    let _ = std::fs::remove_file(&lower);
+    result
+}
+
+/// Whether the filesystem holding `path` (a downloads path, resolved as
+/// usual) distinguishes `One Piece` from `one piece`.
+#[tauri::command]
+fn path_is_case_sensitive(path: String) -> Result<bool, String> {
+    let dir = resolve_downloads_path(&path);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    let dir = std::fs::canonicalize(&dir).map_err(|e| e.to_string())?;
+
+    if let Some(&cached) = lock_unpoisoned(&CASE_SENSITIVITY).as_ref().and_then(|m| m.get(&dir)) {
+        return Ok(cached);
+    }
+    let sensitive = probe_case_sensitive(&dir).map_err(|e| format!("Case probe failed: {e}"))?;
+    lock_unpoisoned(&CASE_SENSITIVITY)
+        .get_or_insert_with(Default::default)
+        .insert(dir, sensitive);
+    Ok(sensitive)
+}
+
+/// Render a path the way the OS shows it: canonical when it exists, and with
+/// native separators on Windows (server-style paths use forward slashes).
+fn native_path_string(path: &Path) -> String {
+    let p = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let s = p.to_string_lossy().into_owned();
+    // canonicalize() on Windows yields a verbatim `\\?\` prefix nobody wants to paste.
+    // Network shares come back as `\\?\UNC\server\share`.
+    #[cfg(target_os = "windows")]
+    let s = if let Some(share) = s.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{share}")
+    } else {
+        s.strip_prefix(r"\\?\").map(str::to_string).unwrap_or(s)
+    }
+    .replace('/', "\\");
+    s
+}
+
+/// Copy a path to the clipboard and return exactly what was copied.
+/// Runs through `resolve_downloads_path` so an empty string yields the
+/// real downloads folder rather than nothing.
+#[tauri::command]
+fn copy_path_to_clipboard(path: String, app: tauri::AppHandle) -> Result<String, String> {
+    let text = native_path_string(&resolve_downloads_path(&path));
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|e| format!("Could not write to clipboard: {e}"))?;
+    Ok(text)
+}
+
+/// Hand a path or URL to the platform's default opener.
+/// `explorer` on Windows, `open` on macOS, `xdg-open` elsewhere.
+fn open_with_os(target: &std::ffi::OsStr) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let opener = "explorer";
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let opener = "xdg-open";
+
+    // spawn() rather than status(): explorer exits non-zero even on success.
+    std::process::Command::new(opener)
+        .arg(target)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Could not launch {opener}: {e}"))
+}
+
+/// Reveal a folder in the native file manager. Forward-slash paths from the
+/// server are accepted on Windows; a missing folder is an error rather
+/// than silently opening somewhere else.
+#[tauri::command]
+fn open_path(path: String) -> Result<(), String> {
+    let trimmed = path.trim().to_string();
+    #[cfg(target_os = "windows")]
+    let trimmed = trimmed.replace('/', "\\");
+    let requested = expand_user_path(&trimmed);
+    let canonical = std::fs::canonicalize(&requested)
+        .map_err(|_| format!("Folder does not exist: {}", requested.display()))?;
+    if !canonical.is_dir() {
+        return Err(format!("Not a folder: {}", requested.display()));
+    }
+    let target = PathBuf::from(native_path_string(&canonical));
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // xdg-open hands off and exits, so its status says whether any
+        // handler took the folder. Otherwise try file managers directly.
+        let handled = std::process::Command::new("xdg-open")
+            .arg(&target)
+            .status()
+            .is_ok_and(|st| st.success());
+        if handled {
+            return Ok(());
+        }
+        let fallbacks = std::env::var("FILE_MANAGER")
+            .ok()
+            .filter(|fm| !fm.trim().is_empty())
+            .into_iter()
+            .chain(["nautilus".to_string(), "dolphin".to_string()]);
+        for fm in fallbacks {
+            if std::process::Command::new(&fm).arg(&target).spawn().is_ok() {
+                return Ok(());
+            }
+        }
+        Err("No file manager found (tried xdg-open, $FILE_MANAGER, nautilus, dolphin)".to_string())
+    }
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    open_with_os(target.as_os_str())
+}
+
+/// Open an http(s) URL in the default browser.
+/// Anything else (file://, javascript:, stray whitespace) is rejected.
+#[tauri::command]
+fn open_url(url: String) -> Result<(), String> {
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("URL must not contain whitespace or control characters".to_string());
+    }
+    let parsed = url::Url::parse(&url).map_err(|e| format!("Invalid URL: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Refusing to open {}:// URL", parsed.scheme()));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err("URL has no host".to_string());
+    }
+    // Pass the re-serialised form so anything odd is percent-encoded.
+    open_with_os(parsed.as_str().as_ref())
+}
+
+/// The true OS-level scale factor for a window.
+/// On Linux this bypasses WebKitGTK's unreliable devicePixelRatio.
+/// On macOS the value comes directly from the native window.
+fn window_scale_factor(window: &tauri::Window) -> f64 {
+    window.scale_factor().unwrap_or(1.0)
+}
+
+/// Initial read of the scale factor; later changes arrive as
+/// `scale-factor-changed`.
+#[tauri::command]
+fn get_scale_factor(window: tauri::Window) -> f64 {
+    let scale_factor = window_scale_factor(&window);
+    LAST_SCALE_FACTOR.store(scale_factor.to_bits(), Ordering::SeqCst);
+    scale_factor
+}
+
+/// Bits of the last scale factor the frontend was told about.
+static LAST_SCALE_FACTOR: AtomicU64 = AtomicU64::new(0);
+
+/// Push a DPI change to the frontend as `scale-factor-changed` so the
+/// reader can re-layout without a restart. Repeats of the last value are
+/// dropped, since moves report the factor on every event.
+fn emit_scale_factor(window: &tauri::Window, scale_factor: f64) {
+    if LAST_SCALE_FACTOR.swap(scale_factor.to_bits(), Ordering::SeqCst) != scale_factor.to_bits() {
+        let _ = window.emit("scale-factor-changed", scale_factor);
+    }
+}
+
+/// Main window geometry kept across launches, in physical pixels.
+#[derive(Serialize, serde::Deserialize, Clone, Copy)]
+pub struct WindowGeometry {
+    x:          i32,
+    y:          i32,
+    width:      u32,
+    height:     u32,
+    maximized:  bool,
+    fullscreen: bool,
+}
+
+const WINDOW_STATE_KEY: &str = "window_state";
+
+/// Last position and size while the window was neither maximized nor
+/// fullscreen. That's what gets saved, so un-maximizing after a restore
+/// returns to the size the user actually chose.
+static NORMAL_GEOMETRY: Mutex<Option<(i32, i32, u32, u32)>> = Mutex::new(None);
+
+/// Track the normal geometry from move/resize events.
+fn track_window_geometry(window: &tauri::Window) {
+    if window.is_maximized().unwrap_or(false) || window.is_fullscreen().unwrap_or(false) {
+        return;
+    }
+    if let (Ok(pos), Ok(size)) = (window.outer_position(), window.inner_size()) {
+        *lock_unpoisoned(&NORMAL_GEOMETRY) = Some((pos.x, pos.y, size.width, size.height));
+    }
+}
+
+fn save_window_state(window: &tauri::Window) {
+    track_window_geometry(window);
+    let Some((x, y, width, height)) = *lock_unpoisoned(&NORMAL_GEOMETRY) else { return };
+    let geometry = WindowGeometry {
+        x,
+        y,
+        width,
+        height,
+        maximized: window.is_maximized().unwrap_or(false),
+        fullscreen: window.is_fullscreen().unwrap_or(false),
+    };
+    if let Err(e) = store_setting(WINDOW_STATE_KEY, serde_json::json!(geometry)) {
+        eprintln!("Could not save window state: {e}");
+    }
+}
+
+/// Reapply the saved geometry, then maximized/fullscreen on top of it so
+/// the normal size is what the window returns to.
+fn restore_window_state(window: &tauri::Window) {
+    let Some(saved) = load_setting::<WindowGeometry>(WINDOW_STATE_KEY) else { return };
+    let mut geometry = saved;
+
+    // Keep the window on the monitor holding its centre. If no connected
+    // monitor's work area holds it (a display was unplugged since), centre
+    // it on the primary monitor instead of opening invisible.
+    let cx = saved.x + saved.width as i32 / 2;
+    let cy = saved.y + saved.height as i32 / 2;
+    let containing = window
+        .available_monitors()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|m| *m.work_area())
+        .find(|a| {
+            (a.position.x..a.position.x + a.size.width as i32).contains(&cx)
+                && (a.position.y..a.position.y + a.size.height as i32).contains(&cy)
+        });
+    let recentre = containing.is_none();
+    let area = containing.or_else(|| window.primary_monitor().ok().flatten().map(|m| *m.work_area()));
+    if let Some(area) = area {
+        geometry.width = geometry.width.min(area.size.width);
+        geometry.height = geometry.height.min(area.size.height);
+        let spare_x = (area.size.width - geometry.width) as i32;
+        let spare_y = (area.size.height - geometry.height) as i32;
+        if recentre {
+            geometry.x = area.position.x + spare_x / 2;
+            geometry.y = area.position.y + spare_y / 2;
+        } else {
+            geometry.x = geometry.x.clamp(area.position.x, area.position.x + spare_x);
+            geometry.y = geometry.y.clamp(area.position.y, area.position.y + spare_y);
+        }
+    }
+    let _ = window.set_size(tauri::PhysicalSize::new(geometry.width, geometry.height));
+    let _ = window.set_position(tauri::PhysicalPosition::new(geometry.x, geometry.y));
+    *lock_unpoisoned(&NORMAL_GEOMETRY) = Some((geometry.x, geometry.y, geometry.width, geometry.height));
+
+    // The window has no native decorations, so if the screen is now
+    // smaller than the saved window, fall back to maximized: fullscreen
+    // there would leave no visible title bar to get out of it with.
+    let shrank = geometry.width < saved.width || geometry.height < saved.height;
+    if saved.fullscreen && !shrank {
+        let _ = window.set_fullscreen(true);
+    } else if saved.maximized || saved.fullscreen {
+        let _ = window.maximize();
+    }
+}
+
+/// The main window, for commands that must act on it whichever window
+/// invoked them.
+fn main_window(app: &tauri::AppHandle) -> Result<tauri::WebviewWindow, String> {
+    app.get_webview_window("main").ok_or_else(|| "Main window not found".to_string())
+}
+
+#[tauri::command]
+fn set_always_on_top(app: tauri::AppHandle, enabled: bool) -> Result<bool, String> {
+    main_window(&app)?.set_always_on_top(enabled).map_err(|e| e.to_string())?;
+    store_setting("always_on_top", enabled.into())?;
+    Ok(enabled)
+}
+
+#[tauri::command]
+fn get_always_on_top(app: tauri::AppHandle) -> Result<bool, String> {
+    main_window(&app)?.is_always_on_top().map_err(|e| e.to_string())
+}
+
+/// Fullscreen state last reported to the frontend.
+static FULLSCREEN: AtomicBool = AtomicBool::new(false);
+
+/// Emit `fullscreen-changed` if the window entered or left fullscreen since
+/// the last check, whether through our commands or F11/native shortcuts.
+/// The scale factor is re-sent too, since some setups change the effective
+/// DPI across the transition.
+fn sync_fullscreen_state(window: &tauri::Window) {
+    let Ok(fullscreen) = window.is_fullscreen() else { return };
+    if FULLSCREEN.swap(fullscreen, Ordering::SeqCst) != fullscreen {
+        let _ = window.emit("fullscreen-changed", fullscreen);
+        emit_scale_factor(window, window.scale_factor().unwrap_or(1.0));
+    }
+}
+
+#[tauri::command]
+fn set_fullscreen(app: tauri::AppHandle, enabled: bool) -> Result<bool, String> {
+    let window = main_window(&app)?;
+    window.set_fullscreen(enabled).map_err(|e| e.to_string())?;
+    sync_fullscreen_state(&window.as_ref().window());
+    Ok(enabled)
+}
+
+#[tauri::command]
+fn toggle_fullscreen(app: tauri::AppHandle) -> Result<bool, String> {
+    let current = main_window(&app)?.is_fullscreen().map_err(|e| e.to_string())?;
+    set_fullscreen(app, !current)
+}
+
+const DEFAULT_WINDOW_TITLE: &str = "Moku";
+const MAX_WINDOW_TITLE_CHARS: usize = 200;
+
+/// Show e.g. "One Piece — Ch. 1100 · Moku" in the taskbar. Control
+/// characters are dropped and long titles truncated; an empty title falls
+/// back to the default.
+#[tauri::command]
+fn set_window_title(app: tauri::AppHandle, title: String) -> Result<String, String> {
+    let cleaned: String = title
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| !c.is_control())
+        .take(MAX_WINDOW_TITLE_CHARS)
+        .collect();
+    let cleaned = cleaned.trim();
+    let title = if cleaned.is_empty() { DEFAULT_WINDOW_TITLE } else { cleaned };
+    main_window(&app)?.set_title(title).map_err(|e| e.to_string())?;
+    Ok(title.to_string())
+}
+
+#[tauri::command]
+fn reset_window_title(app: tauri::AppHandle) -> Result<String, String> {
+    set_window_title(app, String::new())
+}
+
+/// Manual escape hatch for a window that ended up somewhere unreachable.
+#[tauri::command]
+fn center_window(app: tauri::AppHandle) -> Result<(), String> {
+    main_window(&app)?.center().map_err(|e| e.to_string())
+}
+
+#[derive(Serialize, Clone)]
+pub struct MonitorInfo {
+    name:            Option<String>,
+    width:           u32,
+    height:          u32,
+    /// 60 when the platform doesn't report it.
+    refresh_rate_hz: f64,
+    scale_factor:    f64,
+}
+
+/// Refresh rate of the display whose device name Tauri reports
+/// (`\\.\DISPLAY1`), from its current display mode.
+#[cfg(target_os = "windows")]
+fn monitor_refresh_rate(monitor: &tauri::Monitor) -> Option<f64> {
+    use windows_sys::Win32::Graphics::Gdi::{EnumDisplaySettingsW, DEVMODEW, ENUM_CURRENT_SETTINGS};
+    let name: Vec<u16> = monitor.name()?.encode_utf16().chain(std::iter::once(0)).collect();
+    // SAFETY: DEVMODEW is plain data; all-zero is a valid initial value.
+    let mut mode: DEVMODEW = unsafe { std::mem::zeroed() };
+    mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+    // SAFETY: `name` is NUL-terminated and `mode` is correctly sized.
+    if unsafe { EnumDisplaySettingsW(name.as_ptr(), ENUM_CURRENT_SETTINGS, &mut mode) } == 0 {
+        return None;
+    }
+    // 0 and 1 mean "hardware default".
+    Some(f64::from(mode.dmDisplayFrequency)).filter(|hz| *hz > 1.0)
+}
+
+/// From `system_profiler`'s `UI Looks like: 2560 x 1440 @ 144.00Hz` lines,
+/// preferring the one matching this monitor's resolution.
+#[cfg(target_os = "macos")]
+fn monitor_refresh_rate(monitor: &tauri::Monitor) -> Option<f64> {
+    let out = std::process::Command::new("system_profiler")
+        .arg("SPDisplaysDataType")
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let rate = |line: &str| -> Option<f64> {
+        line.rsplit_once('@')?.1.trim().trim_end_matches("Hz").trim().parse().ok()
+    };
+    let size = monitor.size();
+    let wanted = format!("{} x {}", size.width, size.height);
+    text.lines()
+        .filter(|l| l.contains("Hz"))
+        .find(|l| l.contains(&wanted))
+        .or_else(|| text.lines().find(|l| l.contains("Hz")))
+        .and_then(rate)
+}
+
+/// From `xrandr --current`: the output placed at this monitor's position,
+/// and its mode line marked `*` (current).
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn monitor_refresh_rate(monitor: &tauri::Monitor) -> Option<f64> {
+    let out = std::process::Command::new("xrandr").arg("--current").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let pos = monitor.position();
+    let placement = format!("+{}+{}", pos.x, pos.y);
+    let mut in_output = false;
+    for line in text.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            in_output = line.contains(" connected") && line.contains(&placement);
+            continue;
+        }
+        if in_output {
+            if let Some(current) = line.split_whitespace().find(|t| t.contains('*')) {
+                return current.trim_end_matches(['*', '+']).parse().ok();
+            }
+        }
+    }
+    None
+}
+
+fn monitor_info(monitor: &tauri::Monitor) -> MonitorInfo {
+    MonitorInfo {
+        name:            monitor.name().cloned(),
+        width:           monitor.size().width,
+        height:          monitor.size().height,
+        refresh_rate_hz: monitor_refresh_rate(monitor).unwrap_or(60.0),
+        scale_factor:    monitor.scale_factor(),
+    }
+}
+
+/// Refresh rate and resolution of the monitor the main window is on (the
+/// primary one if that can't be determined), for tuning scroll animation.
+#[tauri::command]
+async fn get_primary_monitor_refresh_rate(app: tauri::AppHandle) -> Result<MonitorInfo, String> {
+    let window = main_window(&app)?;
+    let monitor = window
+        .current_monitor()
+        .ok()
+        .flatten()
+        .or_else(|| window.primary_monitor().ok().flatten())
+        .ok_or("No monitor found")?;
+    tauri::async_runtime::spawn_blocking(move || monitor_info(&monitor))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Name and origin of the monitor the window was last seen on.
+static CURRENT_MONITOR: Mutex<Option<(Option<String>, i32, i32)>> = Mutex::new(None);
+
+/// On a move, check whether the window crossed onto another monitor and
+/// if so emit `monitor-changed` with that monitor's details.
+fn track_monitor_change(window: &tauri::Window) {
+    let Some(monitor) = window.current_monitor().ok().flatten() else { return };
+    let key = (monitor.name().cloned(), monitor.position().x, monitor.position().y);
+    let previous = lock_unpoisoned(&CURRENT_MONITOR).replace(key.clone());
+    if previous.is_none_or(|p| p == key) {
+        return;
+    }
+    let window = window.clone();
+    // Rate lookups may shell out, so keep them off the event loop.
+    std::thread::spawn(move || {
+        let _ = window.emit("monitor-changed", monitor_info(&monitor));
+    });
+}
+
+/// Below this the window is close enough to invisible to be unclickable.
+const MIN_WINDOW_OPACITY: f64 = 0.3;
+
+/// Make the whole window translucent through the window system: a layered
+/// window on Windows, widget opacity on GTK (needs a compositor).
+#[cfg(target_os = "windows")]
+fn apply_window_opacity(window: &tauri::WebviewWindow, opacity: f64) -> Result<(), String> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongW, SetLayeredWindowAttributes, SetWindowLongW, GWL_EXSTYLE, LWA_ALPHA, WS_EX_LAYERED,
+    };
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?.0;
+    // SAFETY: `hwnd` is the live handle of our own main window.
+    unsafe {
+        let style = GetWindowLongW(hwnd, GWL_EXSTYLE);
+        SetWindowLongW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED as i32);
+        if SetLayeredWindowAttributes(hwnd, 0, (opacity * 255.0).round() as u8, LWA_ALPHA) == 0 {
+            return Err("Could not change window opacity".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn apply_window_opacity(window: &tauri::WebviewWindow, opacity: f64) -> Result<(), String> {
+    use gtk::prelude::WidgetExt;
+    // GTK objects may only be touched on the main thread.
+    let target = window.clone();
+    window
+        .run_on_main_thread(move || {
+            if let Ok(gtk_window) = target.gtk_window() {
+                gtk_window.set_opacity(opacity);
+            }
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn apply_window_opacity(_window: &tauri::WebviewWindow, _opacity: f64) -> Result<(), String> {
+    Err("Window opacity isn't supported on this platform".to_string())
+}
+
+/// Set the main window's opacity, clamped to 0.3–1.0, and remember it.
+#[tauri::command]
+fn set_window_opacity(app: tauri::AppHandle, opacity: f64) -> Result<f64, String> {
+    if !opacity.is_finite() {
+        return Err(format!("Invalid opacity {opacity}"));
+    }
+    let opacity = opacity.clamp(MIN_WINDOW_OPACITY, 1.0);
+    apply_window_opacity(&main_window(&app)?, opacity)?;
+    store_setting("window_opacity", opacity.into())?;
+    Ok(opacity)
+}
+
+/// Default main window size, matching tauri.conf.json.
+const DEFAULT_WINDOW_SIZE: (f64, f64) = (1280.0, 800.0);
+
+/// Recovery action: default size centred on the primary monitor, and no
+/// saved geometry, always-on-top or opacity override. The resulting
+/// geometry is returned and emitted as `window-reset`.
+#[tauri::command]
+fn reset_window(app: tauri::AppHandle) -> Result<WindowGeometry, String> {
+    let window = main_window(&app)?;
+    let err = |e: tauri::Error| e.to_string();
+    window.set_fullscreen(false).map_err(err)?;
+    window.unmaximize().map_err(err)?;
+    window.set_always_on_top(false).map_err(err)?;
+    if let Err(e) = apply_window_opacity(&window, 1.0) {
+        eprintln!("Could not reset window opacity: {e}");
+    }
+    for key in [WINDOW_STATE_KEY, "always_on_top", "window_opacity"] {
+        store_setting(key, serde_json::Value::Null)?;
+    }
+
+    let scale = window.scale_factor().unwrap_or(1.0);
+    let size = tauri::LogicalSize::new(DEFAULT_WINDOW_SIZE.0, DEFAULT_WINDOW_SIZE.1).to_physical::<u32>(scale);
+    window.set_size(size).map_err(err)?;
+    let position = match window.primary_monitor().map_err(err)? {
+        Some(m) => {
+            let area = *m.work_area();
+            let width = size.width.min(area.size.width);
+            let height = size.height.min(area.size.height);
+            tauri::PhysicalPosition::new(
+                area.position.x + (area.size.width - width) as i32 / 2,
+                area.position.y + (area.size.height - height) as i32 / 2,
+            )
+        }
+        None => tauri::PhysicalPosition::new(0, 0),
+    };
+    window.set_position(position).map_err(err)?;
+
+    *lock_unpoisoned(&NORMAL_GEOMETRY) = Some((position.x, position.y, size.width, size.height));
+    let geometry = WindowGeometry {
+        x:          position.x,
+        y:          position.y,
+        width:      size.width,
+        height:     size.height,
+        maximized:  false,
+        fullscreen: false,
+    };
+    let _ = app.emit("window-reset", geometry);
+    Ok(geometry)
+}
+
+/// The desktop's colour-scheme preference on Linux, read from the XDG
+/// settings portal and then GNOME's gsettings, since WebKitGTK doesn't
+/// report theme changes reliably.
+#[cfg(target_os = "linux")]
+fn linux_system_theme() -> Option<&'static str> {
+    let portal = std::process::Command::new("gdbus")
+        .args([
+            "call", "--session",
+            "--dest", "org.freedesktop.portal.Desktop",
+            "--object-path", "/org/freedesktop/portal/desktop",
+            "--method", "org.freedesktop.portal.Settings.Read",
+            "org.freedesktop.appearance", "color-scheme",
+        ])
+        .output()
+        .ok()
+        .filter(|o| o.status.success());
+    if let Some(out) = portal {
+        // `(<<uint32 1>>,)`: 1 = prefer dark, 2 = prefer light, 0 = no preference.
+        let text = String::from_utf8_lossy(&out.stdout);
+        if text.contains("uint32 1") {
+            return Some("dark");
+        }
+        if text.contains("uint32 2") {
+            return Some("light");
+        }
+    }
+    let gsetting = |key: &str| {
+        std::process::Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", key])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_lowercase())
+    };
+    if let Some(scheme) = gsetting("color-scheme") {
+        if scheme.contains("dark") {
+            return Some("dark");
+        }
+        if scheme.contains("light") {
+            return Some("light");
+        }
+    }
+    let gtk_theme = gsetting("gtk-theme")?;
+    Some(if gtk_theme.contains("dark") { "dark" } else { "light" })
+}
+
+/// `"light"` or `"dark"`, defaulting to dark when nothing can be detected.
+fn detect_system_theme(app: &tauri::AppHandle) -> &'static str {
+    #[cfg(target_os = "linux")]
+    if let Some(theme) = linux_system_theme() {
+        return theme;
+    }
+    match main_window(app).and_then(|w| w.theme().map_err(|e| e.to_string())) {
+        Ok(tauri::Theme::Light) => "light",
+        _ => "dark",
+    }
+}
+
+#[tauri::command]
+fn get_system_theme(app: tauri::AppHandle) -> &'static str {
+    detect_system_theme(&app)
+}
+
+/// Theme last sent as `system-theme-changed`.
+static SYSTEM_THEME: Mutex<Option<&'static str>> = Mutex::new(None);
+
+fn emit_theme_if_changed(app: &tauri::AppHandle, theme: &'static str) {
+    let previous = lock_unpoisoned(&SYSTEM_THEME).replace(theme);
+    if previous.is_some_and(|p| p != theme) {
+        let _ = app.emit("system-theme-changed", theme);
+    }
+}
+
+/// Seed the current theme and, on Linux, poll the desktop setting because
+/// the window's own `ThemeChanged` event doesn't fire there.
+fn start_theme_watch(app: tauri::AppHandle) {
+    emit_theme_if_changed(&app, detect_system_theme(&app));
+    #[cfg(target_os = "linux")]
+    std::thread::spawn(move || loop {
+        std::thread::sleep(std::time::Duration::from_secs(5));
+        if let Some(theme) = linux_system_theme() {
+            emit_theme_if_changed(&app, theme);
+        }
+    });
+}
+
+/// What `kill_tachidesk` actually had to do.
+#[derive(Serialize, Default)]
+pub struct KillReport {
+    /// A server spawned or adopted by this session was being tracked.
+    had_tracked_child: bool,
+    /// The tracked server exited on a polite shutdown request.
+    graceful:          bool,
+    /// The tracked server had to be force-killed.
+    forced:            bool,
+    /// The sweep for other servers using our data dir stopped something.
+    external_kill_ran: bool,
+}
+
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 5;
+const MAX_SHUTDOWN_TIMEOUT_SECS: u64 = 60;
+
+/// How long a server gets to exit on its own before it is force-killed.
+fn shutdown_timeout() -> std::time::Duration {
+    let secs = load_setting::<u64>("shutdown_timeout_secs").unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+    std::time::Duration::from_secs(secs.min(MAX_SHUTDOWN_TIMEOUT_SECS))
+}
+
+#[tauri::command]
+fn set_shutdown_timeout(secs: u64) -> Result<(), String> {
+    if secs > MAX_SHUTDOWN_TIMEOUT_SECS {
+        return Err(format!("Shutdown timeout must be at most {MAX_SHUTDOWN_TIMEOUT_SECS} seconds"));
+    }
+    store_setting("shutdown_timeout_secs", serde_json::json!(secs))
+}
+
+/// Servers started against our data dir, found by the rootDir flag we pass
+/// on spawn. A Suwayomi the user runs separately with its own data dir
+/// doesn't carry it and is left alone.
+fn own_server_pids() -> Vec<u32> {
+    let root_arg = java_property_arg("suwayomi.tachidesk.config.server.rootDir", &suwayomi_data_dir());
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes_specifics(
+        sysinfo::ProcessesToUpdate::All,
+        true,
+        sysinfo::ProcessRefreshKind::new().with_cmd(sysinfo::UpdateKind::OnlyIfNotSet),
+    );
+    let own = sysinfo::Pid::from_u32(std::process::id());
+    sys.processes()
+        .iter()
+        .filter(|(pid, p)| **pid != own && p.cmd().contains(&root_arg))
+        .map(|(pid, _)| pid.as_u32())
+        .collect()
+}
+
+/// Ask a process to exit, returning whether the request was delivered at
+/// all. SIGTERM on Unix. On Windows a Ctrl+C on the process's console, which
+/// runs the JVM's shutdown hooks; only a console-less process (javaw) falls
+/// back to taskkill without /F, which succeeds only if it has a window.
+fn request_exit(pid: u32) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        send_console_ctrl_c(pid)
+            || std::process::Command::new("taskkill")
+                .args(["/PID", &pid.to_string()])
+                .output()
+                .is_ok_and(|out| out.status.success())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let mut sys = sysinfo::System::new();
+        let spid = sysinfo::Pid::from_u32(pid);
+        sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[spid]), true);
+        sys.process(spid).and_then(|p| p.kill_with(sysinfo::Signal::Term)).unwrap_or(false)
+    }
+}
+
+/// Deliver Ctrl+C to every process on `pid`'s console. The shell plugin
+/// spawns with CREATE_NO_WINDOW, so a console-subsystem java.exe has a
+/// hidden console of its own; javaw has none and this returns false.
+#[cfg(target_os = "windows")]
+fn send_console_ctrl_c(pid: u32) -> bool {
+    use windows_sys::Win32::System::Console::{
+        AttachConsole, FreeConsole, GenerateConsoleCtrlEvent, SetConsoleCtrlHandler, CTRL_C_EVENT,
+    };
+    // A process has at most one console, so concurrent callers would detach
+    // each other mid-send.
+    static CONSOLE: Mutex<()> = Mutex::new(());
+    let _console = lock_unpoisoned(&CONSOLE);
+
+    // SAFETY: these calls take no pointers. The null handler makes this
+    // process ignore the Ctrl+C it is about to send to the shared console;
+    // it is left in place because delivery is asynchronous and a GUI app
+    // has no Ctrl+C of its own to lose.
+    unsafe {
+        FreeConsole();
+        if AttachConsole(pid) == 0 {
+            return false;
+        }
+        SetConsoleCtrlHandler(None, 1);
+        let sent = GenerateConsoleCtrlEvent(CTRL_C_EVENT, 0) != 0;
+        FreeConsole();
+        sent
+    }
+}
+
+fn force_kill(pid: u32) -> bool {
+    let mut sys = sysinfo::System::new();
+    let spid = sysinfo::Pid::from_u32(pid);
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[spid]), true);
+    sys.process(spid).is_some_and(|p| p.kill())
+}
+
+/// Stop the server, giving it `shutdown_timeout()` to flush its database
+/// before anything still running is force-killed. Blocks for up to that
+/// long, so async callers should run it on a blocking thread.
+fn kill_tachidesk(app: &tauri::AppHandle) -> KillReport {
+    let mut report = KillReport::default();
+    let state = app.state::<ServerState>();
+    let tracked = state.begin_shutdown();
+    let tracked_pid = tracked.as_ref().map(TrackedServer::pid);
+    let strays: Vec<u32> = own_server_pids().into_iter().filter(|pid| Some(*pid) != tracked_pid).collect();
+    report.had_tracked_child = tracked.is_some();
+
+    // Only processes that got the request can exit on their own; waiting out
+    // the timeout for the rest would just delay the force kill.
+    let asked: Vec<u32> = tracked_pid.iter().chain(&strays).copied().filter(|pid| request_exit(*pid)).collect();
+    let deadline = std::time::Instant::now() + shutdown_timeout();
+    while asked.iter().any(|pid| process_alive(*pid)) && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    if let Some(server) = tracked {
+        if process_alive(server.pid()) {
+            report.forced = server.kill();
+            println!("Force-killed tracked server after shutdown timeout.");
+        } else {
+            report.graceful = true;
+            println!("Tracked server shut down cleanly.");
+        }
+    }
+    let _ = std::fs::remove_file(server_pid_file());
+
+    for pid in strays {
+        report.external_kill_ran |= !process_alive(pid) || force_kill(pid);
+    }
+    report
+}
+
+/// The default server.conf we seed on first launch.
+/// Mirrors the Flatpak wrapper: headless, no tray, no browser pop-up.
+const DEFAULT_SERVER_CONF: &str = r#"server.ip = "127.0.0.1"
+server.port = 4567
+server.webUIEnabled = false
+server.initialOpenInBrowserEnabled = false
+server.systemTrayEnabled = false
+server.webUIInterface = "browser"
+server.webUIFlavor = "WebUI"
+server.webUIChannel = "stable"
+server.electronPath = ""
+server.debugLogsEnabled = false
+server.downloadAsCbz = true
+server.autoDownloadNewChapters = false
+server.globalUpdateInterval = 12
+server.maxSourcesInParallel = 6
+server.extensionRepos = []
+"#;
+
+/// Ensure the Suwayomi data dir and server.conf exist, and that the three
+/// keys that cause GUI/JCEF crashes are always set to safe values.
+/// This mirrors the shell-script logic in the Flatpak's tachidesk-server wrapper.
+fn seed_server_conf(data_dir: &PathBuf) {
+    let conf_path = data_dir.join("server.conf");
+
+    if !conf_path.exists() {
+        if let Err(e) = std::fs::create_dir_all(data_dir) {
+            eprintln!("Could not create Suwayomi data dir: {e}");
+            return;
+        }
+        if let Err(e) = std::fs::write(&conf_path, DEFAULT_SERVER_CONF) {
+            eprintln!("Could not write server.conf: {e}");
+        }
+    }
+
+    // Patch the three critical keys in-place, plus a pinned port so a fresh
+    // default conf doesn't silently move the server back to 4567.
+    let Ok(contents) = std::fs::read_to_string(&conf_path) else { return };
+
+    let mut patched = GUI_SAFE_CONF_KEYS
+        .iter()
+        .fold(contents, |text, (key, value)| patch_conf_key(text, key, value));
+    if let Some(port) = load_setting::<u16>("server_port") {
+        patched = patch_conf_key(patched, "server.port", &port.to_string());
+    }
+
+    let _ = std::fs::write(&conf_path, patched);
+}
+
+/// Keys forced to these values on every launch; any other value makes the
+/// server try to open a GUI/JCEF window and crash.
+const GUI_SAFE_CONF_KEYS: &[(&str, &str)] = &[
+    ("server.webUIEnabled", "false"),
+    ("server.initialOpenInBrowserEnabled", "false"),
+    ("server.systemTrayEnabled", "false"),
+];
+
+/// Conf keys the server re-reads live through its `setSettings` mutation,
+/// paired with the GraphQL field name. Everything else needs a restart.
+const LIVE_CONF_KEYS: &[(&str, &str)] = &[
+    ("server.globalUpdateInterval", "globalUpdateInterval"),
+    ("server.autoDownloadNewChapters", "autoDownloadNewChapters"),
+    ("server.maxSourcesInParallel", "maxSourcesInParallel"),
+    ("server.extensionRepos", "extensionRepos"),
+];
+
+fn live_settings_field(key: &str) -> Option<&'static str> {
+    LIVE_CONF_KEYS.iter().find(|(k, _)| *k == key).map(|(_, field)| *field)
+}
+
+/// The key a `key = value` conf line sets, or `None` for blank lines,
+/// `#`/`//` comments and anything without an `=`.
+fn conf_line_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.starts_with('#') || line.starts_with("//") {
+        return None;
+    }
+    line.split_once('=').map(|(k, _)| k.trim())
+}
+
+/// Replace `key = <value>` in a HOCON/properties-style conf, or append it
+/// if the key is absent. Keys are compared exactly, so `server.port` never
+/// matches `server.portRange` or a commented-out line; when a key is set more
+/// than once only the last (effective) occurrence is rewritten.
+fn patch_conf_key(mut text: String, key: &str, value: &str) -> String {
+    let replacement = format!("{key} = {value}");
+    let mut lines: Vec<&str> = text.lines().collect();
+    if let Some(pos) = lines.iter().rposition(|l| conf_line_key(l) == Some(key)) {
+        let had_newline = text.ends_with('\n');
+        lines[pos] = &replacement;
+        let mut out = lines.join("\n");
+        if had_newline { out.push('\n'); }
+        return out;
+    }
+    // Key absent — append.
+    if !text.is_empty() && !text.ends_with('\n') { text.push('\n'); }
+    text.push_str(&replacement);
+    text.push('\n');
+    text
+}
+
+/// Render a typed value as a HOCON literal: booleans and numbers bare,
+/// strings quoted and escaped, arrays recursively. Keeps `true` from being
+/// written as `"true"`, which the server would read as a string.
+fn conf_literal(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Array(items) => {
+            let inner: Vec<String> = items.iter().map(conf_literal).collect();
+            format!("[{}]", inner.join(", "))
+        }
+        serde_json::Value::Null => "null".to_string(),
+        // serde_json's string encoding matches HOCON's quoted-string escapes.
+        other => other.to_string(),
+    }
+}
+
+/// Read `key`'s value from conf text, with surrounding quotes removed.
+fn read_conf_value(text: &str, key: &str) -> Option<String> {
+    text.lines()
+        .rev()
+        .filter(|l| !l.trim_start().starts_with('#') && !l.trim_start().starts_with("//"))
+        .filter_map(|l| l.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+}
+
+/// Every `key = value` pair in conf text, later duplicates winning.
+fn parse_conf(text: &str) -> std::collections::BTreeMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("//"))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().trim_matches('"').to_string()))
+        .collect()
+}
+
+/// Host and port the local server listens on, per server.conf. A wildcard
+/// bind is reached through loopback.
+fn local_server_addr() -> (String, u16) {
+    let conf = std::fs::read_to_string(suwayomi_data_dir().join("server.conf")).unwrap_or_default();
+    let ip = read_conf_value(&conf, "server.ip")
+        .filter(|ip| !ip.is_empty() && ip != "0.0.0.0" && ip != "::")
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let port = read_conf_value(&conf, "server.port")
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(4567);
+    (ip, port)
+}
+
+/// Base HTTP URL of the local server, e.g. `http://127.0.0.1:4567`.
+fn local_server_url() -> String {
+    let (ip, port) = local_server_addr();
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V6(v6)) => format!("http://[{v6}]:{port}"),
+        _ => format!("http://{ip}:{port}"),
+    }
+}
+
+#[derive(Serialize)]
+pub struct BasicAuth {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct ServerEndpoints {
+    /// e.g. `http://127.0.0.1:4567`
+    base_url:    String,
+    graphql_url: String,
+    /// Subscriptions endpoint (`graphql-transport-ws`).
+    ws_url:      String,
+    /// Credentials when the server has basic auth turned on.
+    auth:        Option<BasicAuth>,
+}
+
+/// Fully-formed server URLs built from the live server.conf, so the
+/// frontend never assembles them itself.
+#[tauri::command]
+fn get_server_endpoints() -> ServerEndpoints {
+    let base_url = local_server_url();
+    let conf = std::fs::read_to_string(suwayomi_data_dir().join("server.conf")).unwrap_or_default();
+    // Newer servers use `authMode`; older ones a `basicAuthEnabled` flag
+    // with `basicAuth*` credentials.
+    let auth_mode = read_conf_value(&conf, "server.authMode").unwrap_or_default();
+    let auth = if auth_mode.eq_ignore_ascii_case("basic_auth") {
+        Some(BasicAuth {
+            username: read_conf_value(&conf, "server.authUsername").unwrap_or_default(),
+            password: read_conf_value(&conf, "server.authPassword").unwrap_or_default(),
+        })
+    } else if read_conf_value(&conf, "server.basicAuthEnabled").as_deref() == Some("true") {
+        Some(BasicAuth {
+            username: read_conf_value(&conf, "server.basicAuthUsername").unwrap_or_default(),
+            password: read_conf_value(&conf, "server.basicAuthPassword").unwrap_or_default(),
+        })
+    } else {
+        None
+    };
+    ServerEndpoints {
+        graphql_url: format!("{base_url}/api/graphql"),
+        ws_url: format!("{}/api/graphql", base_url.replacen("http", "ws", 1)),
+        base_url,
+        auth,
+    }
+}
+
+/// Running WebSocket relay task, aborted by `stop_download_relay`.
+#[derive(Default)]
+struct DownloadRelay(Mutex<Option<tauri::async_runtime::JoinHandle<()>>>);
+
+const DOWNLOAD_SUBSCRIPTION: &str = "subscription { downloadChanged { state queue { \
+    progress state tries chapter { id name sourceOrder } manga { id title } } } }";
+
+/// One `graphql-transport-ws` session: handshake, subscribe, then forward
+/// every `next` payload as `download-progress` until the socket closes.
+async fn relay_download_events(app: &tauri::AppHandle) -> Result<(), String> {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    let endpoints = get_server_endpoints();
+    let mut request = endpoints.ws_url.as_str().into_client_request().map_err(|e| e.to_string())?;
+    let headers = request.headers_mut();
+    headers.insert("Sec-WebSocket-Protocol", "graphql-transport-ws".parse().unwrap());
+    if let Some(auth) = &endpoints.auth {
+        use base64::Engine;
+        let token = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", auth.username, auth.password));
+        if let Ok(value) = format!("Basic {token}").parse() {
+            headers.insert("Authorization", value);
+        }
+    }
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.map_err(|e| e.to_string())?;
+    let send = |msg: serde_json::Value| Message::text(msg.to_string());
+    socket
+        .send(send(serde_json::json!({ "type": "connection_init" })))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    while let Some(msg) = socket.next().await {
+        let msg = msg.map_err(|e| e.to_string())?;
+        if msg.is_close() {
+            break;
+        }
+        let Ok(text) = msg.to_text() else { continue };
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(text) else { continue };
+        match json["type"].as_str() {
+            Some("connection_ack") => {
+                let _ = app.emit("download-relay-status", true);
+                socket
+                    .send(send(serde_json::json!({
+                        "id": "downloads",
+                        "type": "subscribe",
+                        "payload": { "query": DOWNLOAD_SUBSCRIPTION },
+                    })))
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Some("ping") => {
+                let _ = socket.send(send(serde_json::json!({ "type": "pong" }))).await;
+            }
+            Some("next") => {
+                let _ = app.emit("download-progress", &json["payload"]["data"]["downloadChanged"]);
+            }
+            Some("error") => return Err(json["payload"].to_string()),
+            Some("complete") => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Relay the server's download subscription to the frontend as
+/// `download-progress` events, independent of the webview's WebSocket
+/// support. Reconnects with backoff (1s doubling to 30s) whenever the
+/// socket drops; `download-relay-status` reports connect/disconnect.
+#[tauri::command]
+fn start_download_relay(app: tauri::AppHandle, relay: tauri::State<DownloadRelay>) {
+    let mut guard = lock_unpoisoned(&relay.0);
+    if guard.as_ref().is_some_and(|task| !task.inner().is_finished()) {
+        return;
+    }
+    let handle = app.clone();
+    *guard = Some(tauri::async_runtime::spawn(async move {
+        let mut backoff = 1;
+        loop {
+            let started = std::time::Instant::now();
+            if let Err(e) = relay_download_events(&handle).await {
+                eprintln!("Download relay disconnected: {e}");
+            }
+            let _ = handle.emit("download-relay-status", false);
+            // A session that stayed up a while resets the backoff.
+            if started.elapsed().as_secs() > 30 {
+                backoff = 1;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(backoff)).await;
+            backoff = (backoff * 2).min(30);
+        }
+    }));
+}
+
+#[tauri::command]
+fn stop_download_relay(relay: tauri::State<DownloadRelay>) -> bool {
+    match lock_unpoisoned(&relay.0).take() {
+        Some(task) => {
+            task.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Run a GraphQL query or mutation against the local server and return its
+/// `data` object. GraphQL-level errors are surfaced as `Err`.
+async fn local_graphql(query: &str) -> Result<serde_json::Value, String> {
+    local_graphql_with(query, serde_json::Value::Null).await
+}
+
+/// `local_graphql` with GraphQL variables, for values that must not be
+/// spliced into the query text.
+async fn local_graphql_with(query: &str, variables: serde_json::Value) -> Result<serde_json::Value, String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut json: serde_json::Value = client
+        .post(format!("{}/api/graphql", local_server_url()))
+        .json(&serde_json::json!({ "query": query, "variables": variables }))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Server request failed: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected server response: {e}"))?;
+    if let Some(errors) = json.get("errors").and_then(|e| e.as_array()).filter(|e| !e.is_empty()) {
+        let messages: Vec<&str> = errors.iter().filter_map(|e| e["message"].as_str()).collect();
+        return Err(messages.join("; "));
+    }
+    Ok(json["data"].take())
+}
+
+/// Patch a single key in the live server.conf, seeding it first if needed.
+fn write_conf_key(key: &str, value: &str) -> Result<(), String> {
+    let data_dir = suwayomi_data_dir();
+    seed_server_conf(&data_dir);
+    let conf_path = data_dir.join("server.conf");
+    let contents = std::fs::read_to_string(&conf_path)
+        .map_err(|e| format!("Could not read server.conf: {e}"))?;
+    std::fs::write(&conf_path, patch_conf_key(contents, key, value))
+        .map_err(|e| format!("Could not write server.conf: {e}"))
+}
+
+#[derive(Serialize)]
+pub struct ConfKeyResult {
+    key:              String,
+    value:            String,
+    /// The server must be restarted before the new value takes effect.
+    restart_required: bool,
+}
+
+/// Refuse conf writes that could corrupt server.conf or undo the GUI safety
+/// keys: `key` must be a plain dotted name and `value` a single literal
+/// (`12`, `true`, `"text"`, `["a", "b"]`), which is returned parsed.
+fn check_conf_entry(key: &str, value: &str) -> Result<serde_json::Value, String> {
+    let plain_key = key.split('.').all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+    if !plain_key {
+        return Err(format!("Invalid key: {key}"));
+    }
+    if GUI_SAFE_CONF_KEYS.iter().any(|(k, _)| *k == key) {
+        return Err(format!("{key} is managed by Moku and cannot be changed"));
+    }
+    // Conf literals written by Moku are JSON literals, so anything that
+    // doesn't parse as one (stray quotes, `=`, newlines) is refused.
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()
+        .filter(|v| !v.is_object())
+        .ok_or_else(|| format!("Invalid value for {key}"))
+}
+
+/// The literal `set_server_conf` writes for `value`, which may be a conf
+/// literal or a bare string as `get_server_conf` returns it. `None` means
+/// `key` is a GUI safety key already at Moku's value, so there's nothing to
+/// write.
+fn set_conf_literal(key: &str, value: &str) -> Result<Option<String>, String> {
+    if GUI_SAFE_CONF_KEYS.iter().any(|(k, enforced)| *k == key && *enforced == value) {
+        return Ok(None);
+    }
+    let literal = if serde_json::from_str::<serde_json::Value>(value).is_ok() {
+        value.to_string()
+    } else {
+        serde_json::Value::String(value.to_string()).to_string()
+    };
+    check_conf_entry(key, &literal)?;
+    Ok(Some(literal))
+}
+
+/// Write `key` to server.conf and, for keys the server accepts live, push
+/// the same value through GraphQL so no restart is needed. `value` is the
+/// raw conf literal (`12`, `true`, `"text"`, `["a", "b"]`), which is also
+/// valid GraphQL input syntax.
+async fn apply_conf_key(app: &tauri::AppHandle, key: &str, value: &str) -> Result<ConfKeyResult, String> {
+    let value = value.trim();
+    let parsed = check_conf_entry(key, value)?;
+    write_conf_key(key, value)?;
+
+    let restart_required = match live_settings_field(key) {
+        // A stopped server picks the value up from the conf when it starts.
+        Some(_) if !server_is_tracked(app) => false,
+        Some(field) => {
+            let mutation = "mutation($input: SetSettingsInput!) { setSettings(input: $input) { clientMutationId } }";
+            let variables = serde_json::json!({ "input": { "settings": { field: parsed } } });
+            match local_graphql_with(mutation, variables).await {
+                Ok(_) => false,
+                Err(e) => {
+                    eprintln!("Live update of {key} failed, restart needed: {e}");
+                    true
+                }
+            }
+        }
+        None => server_is_tracked(app),
+    };
+    Ok(ConfKeyResult { key: key.to_string(), value: value.to_string(), restart_required })
+}
+
+#[tauri::command]
+async fn set_server_conf_key(
+    app: tauri::AppHandle,
+    key: String,
+    value: String,
+) -> Result<ConfKeyResult, String> {
+    apply_conf_key(&app, key.trim(), &value).await
+}
+
+/// Every key currently set in server.conf, with string values unquoted.
+#[tauri::command]
+fn get_server_conf() -> Result<std::collections::HashMap<String, String>, String> {
+    let data_dir = suwayomi_data_dir();
+    seed_server_conf(&data_dir);
+    let text = std::fs::read_to_string(data_dir.join("server.conf"))
+        .map_err(|e| format!("Could not read server.conf: {e}"))?;
+    Ok(parse_conf(&text).into_iter().collect())
+}
+
+/// Patch several keys at once. Values are conf literals or bare strings, so
+/// the map from `get_server_conf` can be edited and sent straight back; keys
+/// not in `entries` are left untouched. Nothing is written if any entry is
+/// rejected. The server only sees the new values after a restart.
+#[tauri::command]
+fn set_server_conf(entries: std::collections::HashMap<String, String>) -> Result<(), String> {
+    let mut literals = Vec::with_capacity(entries.len());
+    for (key, value) in &entries {
+        if let Some(literal) = set_conf_literal(key.trim(), value.trim())? {
+            literals.push((key.trim(), literal));
+        }
+    }
+
+    let data_dir = suwayomi_data_dir();
+    seed_server_conf(&data_dir);
+    let conf_path = data_dir.join("server.conf");
+    let contents = std::fs::read_to_string(&conf_path)
+        .map_err(|e| format!("Could not read server.conf: {e}"))?;
+    let patched = literals
+        .iter()
+        .fold(contents, |text, (key, literal)| patch_conf_key(text, key, literal));
+    std::fs::write(&conf_path, patched).map_err(|e| format!("Could not write server.conf: {e}"))
+}
+
+/// Suwayomi ignores library update intervals shorter than this (0 disables
+/// automatic updates entirely).
+const MIN_UPDATE_INTERVAL_HOURS: f64 = 6.0;
+
+/// Set how often the server checks the library for new chapters.
+#[tauri::command]
+async fn set_update_interval(app: tauri::AppHandle, hours: f64) -> Result<ConfKeyResult, String> {
+    if !hours.is_finite() || (hours != 0.0 && hours < MIN_UPDATE_INTERVAL_HOURS) {
+        return Err(format!(
+            "Update interval must be at least {MIN_UPDATE_INTERVAL_HOURS} hours (or 0 to disable), got {hours}"
+        ));
+    }
+    apply_conf_key(&app, "server.globalUpdateInterval", &hours.to_string()).await
+}
+
+/// Turn automatic downloading of new chapters on or off.
+#[tauri::command]
+async fn set_auto_download(app: tauri::AppHandle, enabled: bool) -> Result<ConfKeyResult, String> {
+    apply_conf_key(&app, "server.autoDownloadNewChapters", &conf_literal(&enabled.into())).await
+}
+
+/// Choose between CBZ archives and loose image folders for new downloads.
+/// The server only reads this at startup, so a restart is always needed.
+#[tauri::command]
+async fn set_download_as_cbz(app: tauri::AppHandle, enabled: bool) -> Result<ConfKeyResult, String> {
+    let mut result = apply_conf_key(&app, "server.downloadAsCbz", &conf_literal(&enabled.into())).await?;
+    result.restart_required = true;
+    Ok(result)
+}
+
+/// How many sources the server may fetch from at once during updates.
+#[tauri::command]
+async fn set_max_parallel_sources(app: tauri::AppHandle, n: u8) -> Result<ConfKeyResult, String> {
+    if !(1..=16).contains(&n) {
+        return Err(format!("Parallel sources must be between 1 and 16, got {n}"));
+    }
+    apply_conf_key(&app, "server.maxSourcesInParallel", &conf_literal(&n.into())).await
+}
+
+/// Bumped by every `set_debug_logging` call so a pending auto-disable from
+/// an earlier call stands down.
+static DEBUG_LOGGING_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Toggle `server.debugLogsEnabled` and restart the server so it applies.
+/// With `auto_disable_after_secs`, debug logging is switched back off (and
+/// the server restarted again) once the window passes, and a
+/// `debug-logging-auto-disabled` event is emitted.
+#[tauri::command]
+async fn set_debug_logging(
+    app: tauri::AppHandle,
+    enabled: bool,
+    auto_disable_after_secs: Option<u64>,
+) -> Result<(), String> {
+    let generation = DEBUG_LOGGING_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    write_conf_key("server.debugLogsEnabled", &conf_literal(&enabled.into()))?;
+    if server_is_tracked(&app) {
+        restart_server(&app).await?;
+    }
+
+    let Some(secs) = auto_disable_after_secs.filter(|_| enabled) else { return Ok(()) };
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+        if DEBUG_LOGGING_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if let Err(e) = write_conf_key("server.debugLogsEnabled", &conf_literal(&false.into())) {
+            eprintln!("Could not auto-disable debug logging: {e}");
+            return;
+        }
+        if server_is_tracked(&app) {
+            if let Err(e) = restart_server(&app).await {
+                eprintln!("Restart after disabling debug logging failed: {e}");
+            }
+        }
+        let _ = app.emit("debug-logging-auto-disabled", secs);
+    });
+    Ok(())
+}
+
+/// Pin the server to `port`. The port must be unprivileged and free right
+/// now; the pin is kept in Moku's settings so re-seeding server.conf keeps
+/// it, and spawning fails with the holder's name if it's later taken.
+#[tauri::command]
+async fn set_server_port(app: tauri::AppHandle, port: u16) -> Result<ConfKeyResult, String> {
+    if port < 1024 {
+        return Err(format!("Port {port} is out of range (1024-65535)"));
+    }
+    let current = local_server_addr().1;
+    let ours = port == current && server_is_tracked(&app);
+    if !ours {
+        let free = std::net::TcpListener::bind(("127.0.0.1", port)).is_ok();
+        if !free {
+            return Err(tauri::async_runtime::spawn_blocking(move || port_in_use_error(port))
+                .await
+                .map_err(|e| e.to_string())?);
+        }
+    }
+    store_setting("server_port", serde_json::json!(port))?;
+    let mut result = apply_conf_key(&app, "server.port", &port.to_string()).await?;
+    result.restart_required = result.restart_required && port != current;
+    Ok(result)
+}
+
+#[derive(Serialize)]
+pub struct BindAddressResult {
+    ip:      String,
+    /// Set when the server will be reachable from other machines.
+    warning: Option<String>,
+}
+
+/// Set `server.ip`. Takes effect on the next server restart; seeding only
+/// force-patches the GUI keys, so the choice survives later launches.
+#[tauri::command]
+fn set_bind_address(ip: String) -> Result<BindAddressResult, String> {
+    let addr: std::net::IpAddr = ip
+        .trim()
+        .parse()
+        .map_err(|_| format!("{ip:?} is not a valid IP address"))?;
+    write_conf_key("server.ip", &format!("\"{addr}\""))?;
+
+    let warning = (!addr.is_loopback()).then(|| {
+        format!(
+            "The server will listen on {addr} and be reachable from your network. \
+             Enable basic auth in server.conf before exposing your library."
+        )
+    });
+    Ok(BindAddressResult { ip: addr.to_string(), warning })
+}
+
+#[derive(Serialize)]
+pub struct NetworkInterface {
+    name:        String,
+    ipv4:        Vec<String>,
+    ipv6:        Vec<String>,
+    is_loopback: bool,
+    is_virtual:  bool,
+}
+
+/// Best-effort detection of bridges, VPN tunnels and VM adapters, which are
+/// rarely what a user wants to bind to.
+fn is_virtual_interface(name: &str) -> bool {
+    #[cfg(target_os = "linux")]
+    if Path::new("/sys/devices/virtual/net").join(name).exists() {
+        return true;
+    }
+    let lower = name.to_lowercase();
+    [
+        "docker", "veth", "br-", "virbr", "vmnet", "vboxnet", "utun", "tun", "tap",
+        "awdl", "llw", "zt", "tailscale", "wg", "vethernet", "virtualbox", "vmware",
+    ]
+    .iter()
+    .any(|p| lower.starts_with(p))
+}
+
+/// Whether the kernel reports the link as down. Only Linux exposes this
+/// cheaply; elsewhere an interface with an address counts as up.
+fn is_interface_down(_name: &str) -> bool {
+    #[cfg(target_os = "linux")]
+    if let Ok(state) = std::fs::read_to_string(format!("/sys/class/net/{_name}/operstate")) {
+        return state.trim() == "down";
+    }
+    false
+}
+
+/// Interfaces and their addresses, for picking a bind address in Settings.
+/// Down and virtual interfaces are hidden unless `include_all` is set;
+/// loopback is always listed.
+#[tauri::command]
+fn list_network_interfaces(include_all: bool) -> Result<Vec<NetworkInterface>, String> {
+    let addrs = if_addrs::get_if_addrs().map_err(|e| format!("Could not list interfaces: {e}"))?;
+    let mut out: Vec<NetworkInterface> = Vec::new();
+    for iface in addrs {
+        let is_loopback = iface.is_loopback();
+        let is_virtual = !is_loopback && is_virtual_interface(&iface.name);
+        if !include_all && !is_loopback && (is_virtual || is_interface_down(&iface.name)) {
+            continue;
+        }
+        let idx = match out.iter().position(|i| i.name == iface.name) {
+            Some(i) => i,
+            None => {
+                out.push(NetworkInterface {
+                    name: iface.name.clone(),
+                    ipv4: vec![],
+                    ipv6: vec![],
+                    is_loopback,
+                    is_virtual,
+                });
+                out.len() - 1
+            }
+        };
+        match iface.ip() {
+            std::net::IpAddr::V4(ip) => out[idx].ipv4.push(ip.to_string()),
+            std::net::IpAddr::V6(ip) => out[idx].ipv6.push(ip.to_string()),
+        }
+    }
+    Ok(out)
+}
+
+/// Moku's own preferences (things that must outlive a re-seeded
+/// server.conf, window state), kept in the Tauri app-config dir.
+fn moku_settings_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("dev.moku.app")
+        .join("moku-settings.json")
+}
+
+fn load_settings() -> serde_json::Map<String, serde_json::Value> {
+    std::fs::read(moku_settings_path())
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+/// Read one persisted preference, `None` if unset or of the wrong type.
+fn load_setting<T: serde::de::DeserializeOwned>(key: &str) -> Option<T> {
+    load_settings()
+        .remove(key)
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+/// Persist one preference; `Value::Null` removes it.
+fn store_setting(key: &str, value: serde_json::Value) -> Result<(), String> {
+    let mut settings = load_settings();
+    if value.is_null() {
+        settings.remove(key);
+    } else {
+        settings.insert(key.to_string(), value);
+    }
+    let path = moku_settings_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Could not create settings dir: {e}"))?;
+    }
+    let json = serde_json::to_vec_pretty(&settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Could not save settings: {e}"))
+}
+
+/// Set when the data dir isn't the default one: a location chosen through
+/// `migrate_data_dir`, or a temporary fallback when the usual dir turned out
+/// to be unusable this session.
+static DATA_DIR_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Resolve the Suwayomi data directory actually in use this session.
+fn suwayomi_data_dir() -> PathBuf {
+    if let Some(dir) = lock_unpoisoned(&DATA_DIR_OVERRIDE).clone() {
+        return dir;
+    }
+    default_suwayomi_data_dir()
+}
+
+/// Flatpak app ID when running inside a Flatpak sandbox.
+fn flatpak_id() -> Option<String> {
+    if let Ok(id) = std::env::var("FLATPAK_ID") {
+        return Some(id).filter(|id| !id.is_empty());
+    }
+    let info = std::fs::read_to_string("/.flatpak-info").ok()?;
+    // `[Application]` section: `name=dev.moku.app`
+    info.lines()
+        .find_map(|l| l.trim().strip_prefix("name="))
+        .map(|n| n.trim().to_string())
+        .or_else(|| Some("dev.moku.app".to_string()))
+}
+
+/// Resolve the default Suwayomi data directory.
+///
+/// - Flatpak: ~/.var/app/<id>/data/moku/tachidesk  (the wrapper's $XDG_DATA_HOME)
+/// - Linux:   $XDG_DATA_HOME/moku/tachidesk
+/// - macOS:   ~/Library/Application Support/dev.moku.app/tachidesk
+///
+/// When only the other install type's directory holds a library, that one
+/// is used so switching between Flatpak and native keeps the library.
+fn default_suwayomi_data_dir() -> PathBuf {
+    #[cfg(target_os = "macos")]
+    {
+        dirs::data_dir()
+            .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| PathBuf::from("~")))
+            .join("dev.moku.app/tachidesk")
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
+        let id = flatpak_id();
+        // Inside the sandbox $XDG_DATA_HOME is remapped, so the native
+        // location is spelled out against the real home.
+        let native = match &id {
+            Some(_) => home.join(".local/share/moku/tachidesk"),
+            None => xdg_data_home().join("moku/tachidesk"),
+        };
+        let flatpak = home
+            .join(".var/app")
+            .join(id.as_deref().unwrap_or("dev.moku.app"))
+            .join("data/moku/tachidesk");
+
+        let (preferred, other) = if id.is_some() { (flatpak, native) } else { (native, flatpak) };
+        let has_library = |dir: &Path| dir.join("server.conf").is_file();
+        if !has_library(&preferred) && has_library(&other) {
+            return other;
+        }
+        preferred
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        xdg_data_home().join("moku/tachidesk")
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn xdg_data_home() -> PathBuf {
+    std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            dirs::data_dir().unwrap_or_else(|| PathBuf::from("/tmp"))
+        })
+}
+
+#[derive(Serialize)]
+pub struct RuntimeEnvironment {
+    /// `"flatpak"` or `"native"`.
+    kind:       &'static str,
+    flatpak_id: Option<String>,
+    os:         &'static str,
+    data_dir:   String,
+}
+
+#[tauri::command]
+fn get_runtime_environment() -> RuntimeEnvironment {
+    let flatpak_id = flatpak_id();
+    RuntimeEnvironment {
+        kind: if flatpak_id.is_some() { "flatpak" } else { "native" },
+        flatpak_id,
+        os: std::env::consts::OS,
+        data_dir: suwayomi_data_dir().to_string_lossy().into_owned(),
+    }
+}
+
+/// Where the data dir ended up and whether it survives a restart.
+#[derive(Serialize, Clone)]
+pub struct DataDirStatus {
+    path:       String,
+    persistent: bool,
+    warning:    Option<String>,
+}
+
+/// Create `dir` if needed and prove we can write into it.
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(".moku-write-test");
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(&probe)
+}
+
+/// Make sure the data dir is writable, falling back to the temp dir when it
+/// isn't (e.g. locked-down managed Macs). Without this the server silently
+/// never starts.
+fn ensure_writable_data_dir() -> DataDirStatus {
+    let migrated = load_setting::<PathBuf>("data_dir");
+    let preferred = migrated.clone().unwrap_or_else(default_suwayomi_data_dir);
+    let Err(e) = probe_writable(&preferred) else {
+        if migrated.is_some() {
+            *lock_unpoisoned(&DATA_DIR_OVERRIDE) = Some(preferred.clone());
+        }
+        return DataDirStatus {
+            path:       preferred.to_string_lossy().into_owned(),
+            persistent: true,
+            warning:    None,
+        };
+    };
+
+    let fallback = std::env::temp_dir().join("moku/tachidesk");
+    eprintln!("Data dir {preferred:?} is not writable ({e}); falling back to {fallback:?}");
+    let warning = format!(
+        "Could not write to {} ({e}). Using a temporary folder instead — \
+         your library and settings will not be kept after a reboot.",
+        preferred.display()
+    );
+    if let Err(e) = probe_writable(&fallback) {
+        eprintln!("Fallback data dir is not writable either: {e}");
+    }
+    *lock_unpoisoned(&DATA_DIR_OVERRIDE) = Some(fallback.clone());
+    DataDirStatus {
+        path:       fallback.to_string_lossy().into_owned(),
+        persistent: false,
+        warning:    Some(warning),
+    }
+}
+
+/// The effective data dir, plus a warning if it is a non-persistent fallback.
+#[tauri::command]
+fn get_data_dir_status(state: tauri::State<DataDirStatus>) -> DataDirStatus {
+    state.inner().clone()
+}
+
+/// Nearest existing ancestor of `path`, for free-space checks on a target
+/// that hasn't been created yet.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors().find(|p| p.exists()).unwrap_or(path).to_path_buf()
+}
+
+/// Move or copy the whole data dir (library database, server.conf and any
+/// downloads kept inside it) to `new_path` and use it from now on. The
+/// server is stopped first and left stopped. The target must be empty and
+/// have room for everything; a failed copy removes what it wrote and leaves
+/// the original untouched, and with `move_files` the original is only
+/// deleted once the copy is complete.
+#[tauri::command]
+async fn migrate_data_dir(app: tauri::AppHandle, new_path: String, move_files: bool) -> Result<(), String> {
+    let old = suwayomi_data_dir();
+    let new = expand_user_path(new_path.trim());
+    if new.as_os_str().is_empty() {
+        return Err("No target folder given".to_string());
+    }
+    let (old_cmp, new_cmp) = (comparable_path(&old), comparable_path(&new));
+    if old_cmp == new_cmp {
+        return Err("That is already the data folder".to_string());
+    }
+    if Path::new(&new_cmp).starts_with(&old_cmp) || Path::new(&old_cmp).starts_with(&new_cmp) {
+        return Err("The new data folder can't be inside the current one, or contain it".to_string());
+    }
+    if std::fs::read_dir(&new).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("{} is not empty", new.display()));
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let _ = kill_tachidesk(&app);
+
+        // Same filesystem: a single rename moves everything at no cost.
+        let renamed = move_files && {
+            let _ = std::fs::remove_dir(&new);
+            std::fs::rename(&old, &new).is_ok()
+        };
+        if !renamed {
+            let needed = path_bytes(&old);
+            let (_, free) = disk_space_for(&existing_ancestor(&new))?;
+            if free < needed {
+                return Err(format!(
+                    "Not enough space: {} MB needed, {} MB free at {}",
+                    needed / 1_000_000,
+                    free / 1_000_000,
+                    new.display(),
+                ));
+            }
+            if let Err(e) = copy_dir_all(&old, &new) {
+                let _ = std::fs::remove_dir_all(&new);
+                return Err(format!("Could not copy the data folder, nothing was changed: {e}"));
+            }
+        }
+
+        // A downloads path pointing inside the old dir has to follow it.
+        let conf_path = new.join("server.conf");
+        if let Ok(conf) = std::fs::read_to_string(&conf_path) {
+            let downloads = read_conf_value(&conf, "server.downloadsPath").map(PathBuf::from);
+            if let Some(rest) = downloads.as_deref().and_then(|d| d.strip_prefix(&old).ok()) {
+                let moved = conf_literal(&native_path_string(&new.join(rest)).into());
+                let _ = std::fs::write(&conf_path, patch_conf_key(conf.clone(), "server.downloadsPath", &moved));
+            }
+        }
+
+        if let Err(e) = store_setting("data_dir", serde_json::json!(new)) {
+            if renamed {
+                let _ = std::fs::rename(&new, &old);
+            } else {
+                let _ = std::fs::remove_dir_all(&new);
+            }
+            return Err(e);
+        }
+        *lock_unpoisoned(&DATA_DIR_OVERRIDE) = Some(new.clone());
+
+        if move_files && !renamed {
+            if let Err(e) = std::fs::remove_dir_all(&old) {
+                eprintln!("Data dir moved, but the old copy at {old:?} could not be fully removed: {e}");
+            }
+        }
+        println!("Data dir migrated from {old:?} to {new:?}");
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Suwayomi writes its rolling log files under `<rootDir>/logs`.
+fn server_logs_dir() -> PathBuf {
+    suwayomi_data_dir().join("logs")
+}
+
+/// Reveal the server logs folder in the file manager, creating it first so
+/// the command works even before the server has ever run.
+#[tauri::command]
+fn open_logs_folder() -> Result<String, String> {
+    let dir = server_logs_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Could not create logs folder: {e}"))?;
+    open_with_os(dir.as_os_str())?;
+    Ok(native_path_string(&dir))
+}
+
+#[derive(Serialize)]
+pub struct LogMatch {
+    file:        String,
+    /// 1-based line number within `file`.
+    line_number: usize,
+    line:        String,
+}
+
+/// Search the on-disk server logs for `query`, newest file and newest line
+/// first, stopping after `max_results` hits. Matching is case-insensitive;
+/// with `regex: true` the query is a regular expression instead of a
+/// substring.
+#[tauri::command]
+async fn search_logs(
+    query: String,
+    max_results: usize,
+    regex: Option<bool>,
+) -> Result<Vec<LogMatch>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let matcher: Box<dyn Fn(&str) -> bool> = if regex.unwrap_or(false) {
+            let re = regex::RegexBuilder::new(&query)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| format!("Invalid regex: {e}"))?;
+            Box::new(move |line| re.is_match(line))
+        } else {
+            let needle = query.to_lowercase();
+            Box::new(move |line| line.to_lowercase().contains(&needle))
+        };
+
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(server_logs_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            // Rotated logs may be gzipped; only plain text is searched.
+            .filter(|e| e.path().is_file() && e.path().extension().is_none_or(|x| x != "gz"))
+            .map(|e| {
+                let modified = e.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+                (modified, e.path())
+            })
+            .collect();
+        files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+        let mut matches = Vec::new();
+        for (_, path) in files {
+            if matches.len() >= max_results {
+                break;
+            }
+            let Ok(bytes) = std::fs::read(&path) else { continue };
+            let text = String::from_utf8_lossy(&bytes);
+            let lines: Vec<&str> = text.lines().collect();
+            for (idx, line) in lines.iter().enumerate().rev() {
+                if matches.len() >= max_results {
+                    break;
+                }
+                if matcher(line) {
+                    matches.push(LogMatch {
+                        file:        native_path_string(&path),
+                        line_number: idx + 1,
+                        line:        line.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(matches)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Reveal the server data dir (database, server.conf) in the file manager.
+#[tauri::command]
+fn open_data_dir() -> Result<String, String> {
+    let dir = suwayomi_data_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Could not create data folder: {e}"))?;
+    open_with_os(dir.as_os_str())?;
+    Ok(native_path_string(&dir))
+}
+
+/// Conf backups (`server.conf.bak.<unix>`) and data-dir archives
+/// (`moku-backup-*.zip`) are kept here.
+fn backups_dir() -> PathBuf {
+    suwayomi_data_dir().join("backups")
+}
+
+#[derive(Serialize)]
+pub struct BackupEntry {
+    name:          String,
+    path:          String,
+    /// `"conf"` or `"archive"`.
+    kind:          &'static str,
+    bytes:         u64,
+    modified_unix: u64,
+}
+
+fn backup_kind(name: &str) -> Option<&'static str> {
+    if name.contains(".conf.bak.") {
+        Some("conf")
+    } else if name.starts_with("moku-backup-") && name.ends_with(".zip") {
+        Some("archive")
+    } else {
+        None
+    }
+}
+
+/// List conf and data-dir backups, newest first. Also looks next to
+/// server.conf for `.bak.*` files made by hand or by older builds.
+#[tauri::command]
+fn list_backups() -> Result<Vec<BackupEntry>, String> {
+    let mut entries = Vec::new();
+    for dir in [backups_dir(), suwayomi_data_dir()] {
+        let Ok(read) = std::fs::read_dir(&dir) else { continue };
+        for entry in read.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(kind) = backup_kind(&name) else { continue };
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+            let modified_unix = meta.modified().map_or(0, unix_secs);
+            entries.push(BackupEntry {
+                name,
+                path: native_path_string(&entry.path()),
+                kind,
+                bytes: meta.len(),
+                modified_unix,
+            });
+        }
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.modified_unix));
+    Ok(entries)
+}
+
+/// Reveal the backups folder in the file manager, creating it if needed.
+#[tauri::command]
+fn open_backup_folder() -> Result<String, String> {
+    let dir = backups_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Could not create backups folder: {e}"))?;
+    open_with_os(dir.as_os_str())?;
+    Ok(native_path_string(&dir))
+}
+
+/// Copy the live server.conf into `backups/server.conf.bak.<unix>`.
+fn backup_server_conf() -> Result<PathBuf, String> {
+    let dir = backups_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Could not create backups folder: {e}"))?;
+    let dest = dir.join(format!("server.conf.bak.{}", unix_secs(std::time::SystemTime::now())));
+    std::fs::copy(suwayomi_data_dir().join("server.conf"), &dest)
+        .map_err(|e| format!("Could not back up server.conf: {e}"))?;
+    Ok(dest)
+}
+
+/// Save a copy of the current server.conf outside the data dir.
+#[tauri::command]
+fn export_server_conf(output_path: String) -> Result<String, String> {
+    let data_dir = suwayomi_data_dir();
+    seed_server_conf(&data_dir);
+    let dest = expand_user_path(output_path.trim());
+    std::fs::copy(data_dir.join("server.conf"), &dest)
+        .map_err(|e| format!("Could not write {}: {e}", dest.display()))?;
+    Ok(native_path_string(&dest))
+}
+
+/// A GUI safety key the imported file set, and the value Moku wrote instead.
+#[derive(Serialize)]
+pub struct SafetyKeyReset {
+    key:      String,
+    imported: String,
+    applied:  &'static str,
+}
+
+#[derive(Serialize)]
+pub struct ConfImport {
+    backup_path:       String,
+    /// Keys the imported file had set to unsafe values, now force-patched.
+    safety_keys_reset: Vec<SafetyKeyReset>,
+    restart_required:  bool,
+}
+
+/// Prefix of the `import_server_conf` error returned when the file sets GUI
+/// safety keys to values Moku would have to overwrite.
+const CONF_SAFETY_KEYS_ERROR: &str = "conf-safety-keys";
+
+/// Install a server.conf from elsewhere. The file must read as
+/// `key = value` lines (comments and HOCON braces allowed); the current
+/// conf is backed up first. A file that sets a GUI safety key to another
+/// value is refused unless `reset_safety_keys` is set, in which case those
+/// keys are re-applied and listed in the result.
+#[tauri::command]
+fn import_server_conf(input_path: String, reset_safety_keys: Option<bool>) -> Result<ConfImport, String> {
+    let src = expand_user_path(input_path.trim());
+    let text = std::fs::read_to_string(&src).map_err(|e| format!("Could not read {}: {e}", src.display()))?;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let ignorable = line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("//")
+            || line.chars().all(|c| c == '{' || c == '}');
+        if !ignorable && !line.contains(['=', ':']) {
+            return Err(format!("Line {} is not a key/value setting: {line}", n + 1));
+        }
+    }
+    let parsed = parse_conf(&text);
+    if !parsed.keys().any(|k| k.starts_with("server.")) {
+        return Err("File contains no server.* settings".to_string());
+    }
+
+    let safety_keys_reset: Vec<SafetyKeyReset> = GUI_SAFE_CONF_KEYS
+        .iter()
+        .filter_map(|(key, safe)| {
+            let imported = parsed.get(*key).filter(|v| v != safe)?;
+            Some(SafetyKeyReset { key: key.to_string(), imported: imported.clone(), applied: safe })
+        })
+        .collect();
+    if !safety_keys_reset.is_empty() && !reset_safety_keys.unwrap_or(false) {
+        let keys: Vec<String> = safety_keys_reset
+            .iter()
+            .map(|r| format!("{} = {} (Moku needs {})", r.key, r.imported, r.applied))
+            .collect();
+        return Err(format!("{CONF_SAFETY_KEYS_ERROR}: the file sets {}", keys.join(", ")));
+    }
+
+    let data_dir = suwayomi_data_dir();
+    seed_server_conf(&data_dir);
+    let backup = backup_server_conf()?;
+    std::fs::write(data_dir.join("server.conf"), &text).map_err(|e| format!("Could not write server.conf: {e}"))?;
+    seed_server_conf(&data_dir);
+
+    Ok(ConfImport {
+        backup_path: native_path_string(&backup),
+        safety_keys_reset,
+        restart_required: true,
+    })
+}
+
+/// Payload of `backup-progress`, sent per file during export and import.
+#[derive(Serialize, Clone)]
+struct BackupProgress {
+    /// `"export"` or `"import"`.
+    phase:   &'static str,
+    current: String,
+    done:    usize,
+    total:   usize,
+}
+
+/// Database files one of which a restorable backup must contain, next to a
+/// top-level server.conf.
+const BACKUP_DATABASE_NAMES: &[&str] = &["database.mv.db", "database.db", "database.sqlite", "tachidesk.db"];
+
+/// Zip the whole data dir into `moku-backup-<unix>.zip` inside the `dest`
+/// folder. `downloads/` is left out unless `include_downloads` is set, since
+/// it is large and can be downloaded again. Returns the archive path.
+#[tauri::command]
+async fn export_backup(app: tauri::AppHandle, dest: String, include_downloads: Option<bool>) -> Result<String, String> {
+    let include_downloads = include_downloads.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        let data_dir = suwayomi_data_dir();
+        let dest_dir = expand_user_path(dest.trim());
+        std::fs::create_dir_all(&dest_dir).map_err(|e| format!("Could not create {}: {e}", dest_dir.display()))?;
+        let archive = dest_dir.join(format!("moku-backup-{}.zip", unix_secs(std::time::SystemTime::now())));
+
+        let pid_file = server_pid_file();
+        let files: Vec<PathBuf> = WalkDir::new(&data_dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| {
+                let name = e.file_name().to_string_lossy();
+                // Earlier backups would otherwise be nested in every new one.
+                let old_backup = name.starts_with("moku-backup-") && name.ends_with(".zip");
+                let downloads = e.depth() == 1 && name == "downloads";
+                !old_backup && (include_downloads || !downloads)
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && e.path() != archive && e.path() != pid_file)
+            .map(|e| e.into_path())
+            .collect();
+
+        let write = || -> zip::result::ZipResult<()> {
+            let mut out = zip::ZipWriter::new(std::fs::File::create(&archive)?);
+            let total = files.len();
+            for (done, path) in files.iter().enumerate() {
+                let Ok(rel) = path.strip_prefix(&data_dir) else { continue };
+                let name = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let mut file = std::fs::File::open(path)?;
+                let large = file.metadata()?.len() >= u32::MAX as u64;
+                let opts = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .large_file(large);
+                out.start_file(name.as_str(), opts)?;
+                std::io::copy(&mut file, &mut out)?;
+                let _ = app.emit("backup-progress", BackupProgress { phase: "export", current: name, done: done + 1, total });
+            }
+            out.finish()?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            let _ = std::fs::remove_file(&archive);
+            return Err(format!("Could not write backup: {e}"));
+        }
+        Ok(native_path_string(&archive))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Restore a backup made by `export_backup`. The archive must hold a
+/// top-level server.conf and server database. The server is stopped, the
+/// current data dir is set aside as `<dir>.pre-import-<unix>` and put back if
+/// extraction fails; downloads the backup doesn't include are carried over.
+#[tauri::command]
+async fn import_backup(app: tauri::AppHandle, src: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let src = expand_user_path(src.trim());
+        let file = std::fs::File::open(&src).map_err(|e| format!("Could not open {}: {e}", src.display()))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Not a zip archive: {e}"))?;
+        let mut names = Vec::with_capacity(zip.len());
+        for i in 0..zip.len() {
+            let entry = zip.by_index(i).map_err(|e| e.to_string())?;
+            let name = entry
+                .enclosed_name()
+                .ok_or_else(|| format!("Archive entry {} points outside the data folder", entry.name()))?;
+            names.push(name);
+        }
+        let has_top_level = |file: &str| names.iter().any(|n| n == Path::new(file));
+        if !has_top_level("server.conf") || !BACKUP_DATABASE_NAMES.iter().any(|db| has_top_level(db)) {
+            return Err("Archive is not a Moku backup: server.conf or the server database is missing".to_string());
+        }
+        let has_downloads = names.iter().any(|n| n.starts_with("downloads"));
+
+        let _ = kill_tachidesk(&app);
+        let data_dir = suwayomi_data_dir();
+        let mut aside = data_dir.as_os_str().to_owned();
+        aside.push(format!(".pre-import-{}", unix_secs(std::time::SystemTime::now())));
+        let aside = PathBuf::from(aside);
+        if data_dir.exists() {
+            std::fs::rename(&data_dir, &aside).map_err(|e| format!("Could not set the current data aside: {e}"))?;
+        }
+
+        let total = names.len();
+        let extract = |zip: &mut zip::ZipArchive<std::fs::File>| -> Result<(), String> {
+            for (i, name) in names.iter().enumerate() {
+                let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+                let target = data_dir.join(name);
+                if entry.is_dir() {
+                    std::fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+                } else {
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+                    let mut out = std::fs::File::create(&target).map_err(|e| e.to_string())?;
+                    std::io::copy(&mut entry, &mut out).map_err(|e| format!("Could not extract {}: {e}", name.display()))?;
+                }
+                let current = name.to_string_lossy().into_owned();
+                let _ = app.emit("backup-progress", BackupProgress { phase: "import", current, done: i + 1, total });
+            }
+            Ok(())
+        };
+        if let Err(e) = extract(&mut zip) {
+            let _ = std::fs::remove_dir_all(&data_dir);
+            if aside.exists() {
+                let _ = std::fs::rename(&aside, &data_dir);
+            }
+            return Err(format!("Restore failed, previous data kept: {e}"));
+        }
+
+        if !has_downloads && aside.join("downloads").is_dir() {
+            if let Err(e) = std::fs::rename(aside.join("downloads"), data_dir.join("downloads")) {
+                eprintln!("Could not carry downloads over from {aside:?}: {e}");
+            }
+        }
+        seed_server_conf(&data_dir);
+        println!("Backup restored from {src:?}; previous data kept at {aside:?}");
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Build a `-D<name>=<path>` JVM flag as a single argv entry.
+///
+/// The flag is never joined into a command line: std's `Command` hands each
+/// arg to the child separately and, on Windows, applies the MSVC quoting
+/// rules itself, so spaces (`C:\Users\John Smith`), quotes and backslashes
+/// survive intact. It stays an `OsString` so non-UTF-8 or unusual Unicode
+/// paths aren't mangled by a lossy conversion. Trailing separators are
+/// dropped because `...\"` is where Windows quoting goes wrong for any
+/// launcher that re-parses its arguments.
+fn java_property_arg(name: &str, path: &Path) -> std::ffi::OsString {
+    let normalized: PathBuf = path.components().collect();
+    let mut arg = std::ffi::OsString::from(format!("-D{name}="));
+    arg.push(normalized.as_os_str());
+    arg
+}
+
+/// Everything needed to spawn the server process.
+struct ServerInvocation {
+    /// Path to the executable (the bundled java.exe or javaw.exe on Windows, the sidecar script on macOS/Linux).
+    bin: std::ffi::OsString,
+    /// Extra args prepended before the Suwayomi rootDir flag.
+    /// On Windows: ["-jar", "<path-to-jar>"]
+    /// Elsewhere: []
+    prefix_args: Vec<std::ffi::OsString>,
+    /// Working directory for the child process.
+    /// On Windows this must be the bundle folder so javaw can find the JRE and jar.
+    /// Elsewhere: None (inherit).
+    working_dir: Option<PathBuf>,
+    java:        JavaRuntime,
+}
+
+/// Where the server invocation gets its Java runtime.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JavaRuntime {
+    /// `bin` ships its own (the macOS and Windows sidecars) or is the user's
+    /// own binary.
+    Bundled,
+    /// `bin` is a launcher script that runs whatever Java it finds;
+    /// `JAVA_HOME` points it at the one `check_java` picked.
+    Environment,
+    /// `bin` is a placeholder for the Java executable, replaced with the one
+    /// `check_java` picked.
+    Executable,
+}
+
+/// Resolve the server binary path.
+///
+/// If the frontend passes a non-empty `binary` string (user override in
+/// Settings) we always use that — on Linux this is the nixpkgs/Flatpak path.
+///
+/// Otherwise we look for the Tauri-bundled sidecar inside the resource dir
+/// and, on Windows, build the javaw + jar invocation from the suwayomi-bundle.
+fn resolve_server_binary(
+    binary: &str,
+    app: &tauri::AppHandle,
+) -> Result<ServerInvocation, String> {
+    if !binary.trim().is_empty() {
+        return Ok(ServerInvocation {
+            bin: std::ffi::OsString::from(binary),
+            prefix_args: vec![],
+            working_dir: None,
+            java: JavaRuntime::Bundled,
+        });
+    }
+
+    // ── A server version picked in Settings wins over the bundled one ──
+    if let Some(jar) = active_server_version().and_then(|v| server_version_jar(&servers_dir().join(v))) {
+        return Ok(ServerInvocation {
+            bin: java_binary(),
+            prefix_args: vec!["-jar".into(), jar.into_os_string()],
+            working_dir: None,
+            java: JavaRuntime::Executable,
+        });
+    }
+
+    let resource_dir = app
+        .path()
+        .resource_dir()
+        .map_err(|e| format!("Could not locate resource dir: {e}"))?;
+
+    // ── Windows: invoke the bundled java with -jar Suwayomi-Launcher.jar ──
+    #[cfg(target_os = "windows")]
+    {
+        let sidecar = resource_dir.join("suwayomi-server-x86_64-pc-windows-msvc.exe");
+        let bundle_dir = resource_dir.join("suwayomi-bundle");
+        let jar = bundle_dir.join("Suwayomi-Launcher.jar");
+        // The bundle's console java.exe can be sent Ctrl+C to shut down
+        // cleanly; the javaw sidecar can only be force-killed.
+        let console_java = bundle_dir.join("jre").join("bin").join("java.exe");
+        let bin = if console_java.exists() { console_java } else { sidecar };
+
+        if bin.exists() && jar.exists() {
+            return Ok(ServerInvocation {
+                bin: bin.into_os_string(),
+                prefix_args: vec!["-jar".into(), jar.into_os_string()],
+                working_dir: Some(bundle_dir),
+                java: JavaRuntime::Bundled,
+            });
+        }
+    }
+
+    // ── macOS / Linux: sidecar script is self-contained ──
+    let candidates = [
+        "suwayomi-server-aarch64-apple-darwin",
+        "suwayomi-server-x86_64-apple-darwin",
+        // plain name as a dev/Linux fallback
+        "suwayomi-server",
+    ];
+
+    for name in &candidates {
+        let p = resource_dir.join(name);
+        if p.exists() {
+            return Ok(ServerInvocation {
+                bin: p.into_os_string(),
+                prefix_args: vec![],
+                working_dir: None,
+                // The macOS builds bundle a JRE; the plain script uses the system one.
+                java: if *name == "suwayomi-server" { JavaRuntime::Environment } else { JavaRuntime::Bundled },
+            });
+        }
+    }
+
+    Err("Suwayomi server binary not found. Please set the path in Settings.".to_string())
+}
+
+/// How many times `spawn_server` tries before giving up on a transient error.
+const SPAWN_ATTEMPTS: u32 = 3;
+
+/// Payload of the `server-spawn-retry` event.
+#[derive(Serialize, Clone)]
+struct SpawnRetry {
+    attempt:      u32,
+    max_attempts: u32,
+    delay_ms:     u64,
+    error:        String,
+}
+
+/// `spawn()` errors worth retrying: the OS briefly refusing to fork. A
+/// missing binary or Java won't fix itself. A port still held by the previous
+/// server is checked separately before each attempt.
+fn is_transient_spawn_error(err: &tauri_plugin_shell::Error) -> bool {
+    use std::io::ErrorKind;
+    if let tauri_plugin_shell::Error::Io(io) = err {
+        if matches!(
+            io.kind(),
+            ErrorKind::AddrInUse | ErrorKind::WouldBlock | ErrorKind::Interrupted | ErrorKind::ResourceBusy
+        ) {
+            return true;
+        }
+    }
+    let msg = err.to_string().to_lowercase();
+    msg.contains("address already in use") || msg.contains("resource temporarily unavailable")
+}
+
+/// Prefix of the `check_java` (and so `spawn_server`) error returned when no
+/// suitable Java runtime is installed.
+const JAVA_MISSING_ERROR: &str = "java-missing";
+
+/// Prefix of the `spawn_server` error returned when the data disk is nearly full.
+const DISK_FULL_ERROR: &str = "disk-space-critical";
+const DEFAULT_MIN_FREE_DISK_MB: u64 = 100;
+
+fn min_free_disk_bytes() -> u64 {
+    load_setting::<u64>("min_free_disk_mb").unwrap_or(DEFAULT_MIN_FREE_DISK_MB) * 1_000_000
+}
+
+/// Set the free-space floor below which `spawn_server` refuses to start;
+/// 0 disables the check.
+#[tauri::command]
+fn set_min_free_disk_space(mb: u64) -> Result<u64, String> {
+    store_setting("min_free_disk_mb", mb.into())?;
+    Ok(mb)
+}
+
+/// Binary argument of the most recent `spawn_server` call, so the backend
+/// can restart the server on its own after a conf change.
+static LAST_SERVER_BINARY: Mutex<Option<String>> = Mutex::new(None);
+
+/// Stop the server and start it again with the binary it was last spawned
+/// with. Waits for the old process to release the port so the new one
+/// isn't mistaken for an orphan and adopted.
+async fn restart_server(app: &tauri::AppHandle) -> Result<(), String> {
+    let binary = lock_unpoisoned(&LAST_SERVER_BINARY)
+        .clone()
+        .ok_or("Server has not been started in this session")?;
+    let handle = app.clone();
+    let _ = tauri::async_runtime::spawn_blocking(move || kill_tachidesk(&handle)).await;
+    for _ in 0..20 {
+        if !tauri::async_runtime::spawn_blocking(server_port_open).await.unwrap_or(false) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+    spawn_server(binary, app.clone()).await.map(|_| ())
+}
+
+/// How many ports above the configured one `choose_server_port` tries.
+const PORT_SCAN_RANGE: u16 = 50;
+
+fn port_bindable(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// The port the new server should listen on. The configured port is kept
+/// when it's free, including after a short wait for a socket a crashed
+/// server left in TIME_WAIT (which refuses connections but can't be bound
+/// yet). If something is really listening there, the next free port is
+/// written to server.conf instead, unless the user pinned the port in
+/// Settings, in which case that's an error.
+async fn choose_server_port(app: &tauri::AppHandle) -> Result<u16, String> {
+    let configured = local_server_addr().1;
+    let held = tauri::async_runtime::spawn_blocking(move || {
+        if port_bindable(configured) {
+            return false;
+        }
+        if !server_port_open() {
+            for _ in 0..10 {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                if port_bindable(configured) {
+                    return false;
+                }
+            }
+        }
+        true
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if !held {
+        return Ok(configured);
+    }
+
+    let in_use = move || port_in_use_error(configured);
+    if load_setting::<u16>("server_port").is_some() {
+        return Err(tauri::async_runtime::spawn_blocking(in_use).await.map_err(|e| e.to_string())?);
+    }
+    let candidates = configured.saturating_add(1)..=configured.saturating_add(PORT_SCAN_RANGE);
+    let Some(port) = candidates.into_iter().find(|p| port_bindable(*p)) else {
+        return Err(tauri::async_runtime::spawn_blocking(in_use).await.map_err(|e| e.to_string())?);
+    };
+    write_conf_key("server.port", &port.to_string())?;
+    println!("Port {configured} is taken, moving the server to {port}");
+    let _ = app.emit("server-port-changed", port);
+    Ok(port)
+}
+
+#[tauri::command]
+async fn spawn_server(binary: String, app: tauri::AppHandle) -> Result<u16, String> {
+    let state = app.state::<ServerState>();
+    // Claim the spawn before checking for a server: whoever held the flag
+    // stored its child before releasing it, so the check below sees it.
+    if state.spawning.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+        println!("Server spawn already in progress, skipping spawn.");
+        return Ok(local_server_addr().1);
+    }
+    let _spawning = SpawningGuard(&state.spawning);
+    {
+        let guard = state.lock();
+        if guard.is_some() {
+            println!("Server already running, skipping spawn.");
+            return Ok(local_server_addr().1);
+        }
+    }
+
+    *lock_unpoisoned(&LAST_SERVER_BINARY) = Some(binary.clone());
+    state.shutdown_requested.store(false, Ordering::SeqCst);
+
+    // Seed server.conf before launching so Suwayomi starts in headless mode.
+    let data_dir = suwayomi_data_dir();
+    emit_setup_progress(&app, "seeding_config", "Seeding config");
+    seed_server_conf(&data_dir);
+
+    // A PID file means the last session didn't shut its server down. If that
+    // server is still alive, take it over instead of starting a duplicate.
+    if let Some(pid) = find_orphaned_server().await {
+        println!("Adopting server left running by a previous session (pid {pid})");
+        *state.lock() = Some(TrackedServer::Adopted(pid));
+        let _ = app.emit("server-adopted", pid);
+        watch_server_ready(app.clone());
+        return Ok(local_server_addr().1);
+    }
+
+    // Otherwise it died without a clean kill, which is exactly when the
+    // database is most likely to be damaged.
+    if server_pid_file().exists() {
+        if let Ok(check) = run_database_check() {
+            if !check.ok {
+                let _ = app.emit("server-database-corrupt", check.clone());
+                return Err(format!("{DB_CORRUPT_ERROR}: {}", check.errors.join("; ")));
+            }
+        }
+    }
+
+    // Anything still holding the port isn't ours to adopt, and the new
+    // server would just fail to bind.
+    let port = choose_server_port(&app).await?;
+
+    // The server writes its database straight away; starting it on a full
+    // disk only produces confusing errors further down.
+    let floor = min_free_disk_bytes();
+    if let Ok((_, free)) = disk_space_for(&data_dir) {
+        if free < floor {
+            return Err(format!(
+                "{DISK_FULL_ERROR}: only {} MB free on the data disk ({} MB needed). Free up space and try again.",
+                free / 1_000_000,
+                floor / 1_000_000,
+            ));
+        }
+    }
+
+    let mut invocation = resolve_server_binary(&binary, &app)?;
+    let mut java_home = None;
+    if invocation.java != JavaRuntime::Bundled {
+        let handle = app.clone();
+        let java = tauri::async_runtime::spawn_blocking(move || find_java(&handle))
+            .await
+            .map_err(|e| e.to_string())??;
+        let java_path = PathBuf::from(&java.path);
+        if java_path.is_absolute() {
+            java_home = java_path.parent().and_then(Path::parent).map(Path::to_path_buf);
+        }
+        if invocation.java == JavaRuntime::Executable {
+            invocation.bin = java_path.into_os_string();
+        }
+    }
+    let shell = app.shell();
+
+    // Build the full arg list: prefix_args (e.g. -jar foo.jar) + rootDir flag.
+    let args: Vec<std::ffi::OsString> = invocation
+        .prefix_args
+        .into_iter()
+        .chain(std::iter::once(java_property_arg(
+            "suwayomi.tachidesk.config.server.rootDir",
+            &data_dir,
+        )))
+        .collect();
+
+    // On Windows, set the working directory to the bundle folder so javaw.exe
+    // can resolve the JRE and jar relative paths correctly.
+    let working_dir = invocation
+        .working_dir
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+    let build_cmd = || {
+        let cmd = shell
+            .command(&invocation.bin)
+            .env("JAVA_TOOL_OPTIONS", "-Djava.awt.headless=true")
+            .args(&args)
+            .current_dir(&working_dir);
+        match &java_home {
+            Some(home) => cmd.env("JAVA_HOME", home),
+            None => cmd,
+        }
+    };
+
+    emit_setup_progress(&app, "starting_server", "Starting server");
+    let mut attempt = 1;
+    let (rx, child) = loop {
+        // A server that just exited can hold the port a moment longer. The
+        // new one would spawn fine and then die on bind, which `spawn()`
+        // never reports, so check the port before every attempt.
+        let port_busy = !port_bindable(port);
+        let spawned = if port_busy {
+            Err((true, format!("port {port} is still in use")))
+        } else {
+            build_cmd().spawn().map_err(|e| (is_transient_spawn_error(&e), e.to_string()))
+        };
+        match spawned {
+            Ok(spawned) => break spawned,
+            Err((true, error)) if attempt < SPAWN_ATTEMPTS => {
+                let delay_ms = 500 * 2u64.pow(attempt - 1);
+                eprintln!("Spawn attempt {attempt} failed ({error}), retrying in {delay_ms}ms");
+                let _ = app.emit("server-spawn-retry", SpawnRetry {
+                    attempt,
+                    max_attempts: SPAWN_ATTEMPTS,
+                    delay_ms,
+                    error,
+                });
+                tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+                attempt += 1;
+            }
+            Err(_) if port_busy => {
+                return Err(tauri::async_runtime::spawn_blocking(move || port_in_use_error(port))
+                    .await
+                    .map_err(|e| e.to_string())?);
+            }
+            Err((_, error)) => {
+                eprintln!("Failed to spawn {:?}: {}", invocation.bin, error);
+                return Err(error);
+            }
+        }
+    };
+
+    println!("Spawned server: {:?}", invocation.bin);
+    relay_server_output(app.clone(), rx, child.pid());
+    let _ = std::fs::write(server_pid_file(), child.pid().to_string());
+    *state.lock() = Some(TrackedServer::Child(child));
+    watch_server_ready(app.clone());
+    Ok(port)
+}
+
+/// How `server-ready` decides the server is up.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ReadinessProbe {
+    /// The port accepts connections. Fast, but the API may not be serving yet.
+    Tcp,
+    /// A minimal GraphQL query returns valid JSON.
+    Graphql,
+}
+
+fn readiness_probe() -> ReadinessProbe {
+    load_setting("readiness_probe").unwrap_or(ReadinessProbe::Graphql)
+}
+
+#[tauri::command]
+fn set_readiness_probe(mode: ReadinessProbe) -> Result<(), String> {
+    store_setting("readiness_probe", serde_json::json!(mode))
+}
+
+/// One readiness check against `host:port`.
+async fn server_ready(host: &str, port: u16, probe: ReadinessProbe) -> bool {
+    let addr = format!("{host}:{port}");
+    let connect = tokio::time::timeout(std::time::Duration::from_millis(500), tokio::net::TcpStream::connect(&addr));
+    if !matches!(connect.await, Ok(Ok(_))) {
+        return false;
+    }
+    if probe == ReadinessProbe::Tcp {
+        return true;
+    }
+    let Ok(client) = reqwest::Client::builder().timeout(std::time::Duration::from_secs(2)).build() else {
+        return false;
+    };
+    let host = if host.contains(':') { format!("[{host}]") } else { host.to_string() };
+    let resp = client
+        .post(format!("http://{host}:{port}/api/graphql"))
+        .json(&serde_json::json!({ "query": "{ aboutServer { version } }" }))
+        .send()
+        .await;
+    match resp {
+        Ok(r) if r.status().is_success() => r.json::<serde_json::Value>().await.is_ok_and(|j| j["data"].is_object()),
+        _ => false,
+    }
+}
+
+/// Error returned by `wait_for_server` when the server never came up.
+const SERVER_START_TIMEOUT_ERROR: &str = "server-start-timeout";
+
+/// Wait until the server on `port` passes the configured readiness probe,
+/// retrying with a short backoff. Fails with `server-start-timeout` once
+/// `timeout_ms` has elapsed.
+#[tauri::command]
+async fn wait_for_server(port: u16, timeout_ms: u64) -> Result<(), String> {
+    let probe = readiness_probe();
+    let (host, _) = local_server_addr();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+    let mut delay = 100;
+    loop {
+        if server_ready(&host, port, probe).await {
+            return Ok(());
+        }
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return Err(format!("{SERVER_START_TIMEOUT_ERROR}: server did not respond on port {port} within {timeout_ms} ms"));
+        }
+        let wait = std::time::Duration::from_millis(delay).min(deadline - now);
+        tokio::time::sleep(wait).await;
+        delay = (delay * 2).min(1000);
+    }
+}
+
+/// Poll until the freshly started server passes the configured probe, then
+/// emit `server-ready`. Gives up after two minutes or once the server is
+/// no longer tracked.
+fn watch_server_ready(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let probe = readiness_probe();
+        let (host, port) = local_server_addr();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(120);
+        emit_setup_progress(&app, "waiting_for_server", "Waiting for server");
+        while std::time::Instant::now() < deadline && server_is_tracked(&app) {
+            if server_ready(&host, port, probe).await {
+                app.state::<ServerState>().crash_restarts.store(0, Ordering::SeqCst);
+                emit_setup_progress(&app, "ready", "Ready");
+                let _ = app.emit("server-ready", port);
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        }
+    });
+}
+
+#[derive(Clone, Serialize)]
+pub struct ServerLogLine {
+    /// `"stdout"` or `"stderr"`.
+    stream: &'static str,
+    /// Increases by one per line for the whole session, so the UI can drop
+    /// lines it already has from `get_recent_logs`.
+    seq:    u64,
+    line:   String,
+}
+
+const LOG_BUFFER_LINES: usize = 500;
+
+/// The last `LOG_BUFFER_LINES` server output lines, for a logs panel opened
+/// after startup.
+#[derive(Default)]
+struct ServerLogBuffer {
+    lines:    Mutex<std::collections::VecDeque<ServerLogLine>>,
+    next_seq: AtomicU64,
+}
+
+impl ServerLogBuffer {
+    fn push(&self, stream: &'static str, line: &str) -> ServerLogLine {
+        let entry = ServerLogLine {
+            stream,
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            line: line.to_string(),
+        };
+        let mut lines = lock_unpoisoned(&self.lines);
+        if lines.len() == LOG_BUFFER_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(entry.clone());
+        entry
+    }
+}
+
+#[tauri::command]
+fn get_recent_logs(buffer: tauri::State<ServerLogBuffer>) -> Vec<ServerLogLine> {
+    lock_unpoisoned(&buffer.lines).iter().cloned().collect()
+}
+
+/// A server log line split into Suwayomi's logback fields. Lines that don't
+/// match the format (stack traces, JVM banners) keep only `message` and
+/// `raw: true`.
+#[derive(Clone, Serialize)]
+struct StructuredLogLine {
+    stream:    &'static str,
+    seq:       u64,
+    timestamp: Option<String>,
+    level:     Option<String>,
+    thread:    Option<String>,
+    logger:    Option<String>,
+    message:   String,
+    raw:       bool,
+}
+
+const LOG_LEVELS: &[&str] = &["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+/// Parse `<timestamp> [<thread>] <LEVEL> <logger> - <message>`, the pattern
+/// Suwayomi's logback config uses. The timestamp may be a bare time or a
+/// date and time, so it is everything before the thread bracket.
+fn parse_server_log_line(line: &str) -> Option<(String, String, String, String, String)> {
+    let (timestamp, rest) = line.split_once(" [")?;
+    let timestamp = timestamp.trim();
+    if timestamp.is_empty() || !timestamp.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let (thread, rest) = rest.split_once("] ")?;
+    let rest = rest.trim_start();
+    let (level, rest) = rest.split_once(char::is_whitespace)?;
+    if !LOG_LEVELS.contains(&level) {
+        return None;
+    }
+    let (logger, message) = rest.trim_start().split_once(" - ")?;
+    Some((
+        timestamp.to_string(),
+        level.to_string(),
+        thread.to_string(),
+        logger.trim().to_string(),
+        message.to_string(),
+    ))
+}
+
+fn structure_log_line(entry: &ServerLogLine) -> StructuredLogLine {
+    let (stream, seq, line) = (entry.stream, entry.seq, entry.line.as_str());
+    match parse_server_log_line(line) {
+        Some((timestamp, level, thread, logger, message)) => StructuredLogLine {
+            stream,
+            seq,
+            timestamp: Some(timestamp),
+            level: Some(level),
+            thread: Some(thread),
+            logger: Some(logger),
+            message,
+            raw: false,
+        },
+        None => StructuredLogLine {
+            stream,
+            seq,
+            timestamp: None,
+            level: None,
+            thread: None,
+            logger: None,
+            message: line.to_string(),
+            raw: true,
+        },
+    }
+}
+
+/// Forward the child's stdout/stderr to the frontend, one event per line:
+/// `server-log` carries the unprocessed text and `server-log-structured`
+/// the parsed fields.
+fn relay_server_output(
+    app: tauri::AppHandle,
+    mut rx: tauri::async_runtime::Receiver<tauri_plugin_shell::process::CommandEvent>,
+    pid: u32,
+) {
+    use tauri_plugin_shell::process::CommandEvent;
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let (stream, bytes) = match event {
+                CommandEvent::Stdout(b) => ("stdout", b),
+                CommandEvent::Stderr(b) => ("stderr", b),
+                CommandEvent::Terminated(payload) => {
+                    handle_server_exit(&app, pid, payload.code);
+                    break;
+                }
+                _ => continue,
+            };
+            let text = String::from_utf8_lossy(&bytes);
+            let buffer = app.state::<ServerLogBuffer>();
+            for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                let entry = buffer.push(stream, line);
+                let _ = app.emit("server-log-structured", structure_log_line(&entry));
+                let _ = app.emit("server-log", entry);
+            }
+        }
+    });
+}
+
+#[derive(Serialize)]
+pub struct ServerStatus {
+    running: bool,
+    pid:     Option<u32>,
+    port:    u16,
+}
+
+fn process_alive(pid: u32) -> bool {
+    let mut sys = sysinfo::System::new();
+    let spid = sysinfo::Pid::from_u32(pid);
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[spid]), true);
+    sys.process(spid).is_some()
+}
+
+/// Whether the server is really up: the tracked process must still exist
+/// (a stale entry is dropped), and an untracked server still counts if
+/// something answers on the port.
+#[tauri::command]
+async fn get_server_status(app: tauri::AppHandle) -> ServerStatus {
+    let port = local_server_addr().1;
+    let tracked = app.state::<ServerState>().lock().as_ref().map(TrackedServer::pid);
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Some(pid) = tracked {
+            if process_alive(pid) {
+                return ServerStatus { running: true, pid: Some(pid), port };
+            }
+            let state = app.state::<ServerState>();
+            let mut guard = state.lock();
+            if guard.as_ref().is_some_and(|s| s.pid() == pid) {
+                *guard = None;
+                let _ = std::fs::remove_file(server_pid_file());
+            }
+        }
+        ServerStatus { running: server_port_open(), pid: None, port }
+    })
+    .await
+    .unwrap_or(ServerStatus { running: false, pid: None, port })
+}
+
+/// Watchdog restarts allowed before giving up until the server next comes up.
+const MAX_CRASH_RESTARTS: u32 = 5;
+
+#[tauri::command]
+fn set_auto_restart(enabled: bool) -> Result<(), String> {
+    store_setting("auto_restart", enabled.into())
+}
+
+/// Payload of `server-exited`.
+#[derive(Serialize, Clone)]
+struct ServerExit {
+    pid:        u32,
+    code:       Option<i32>,
+    /// The watchdog will try to start the server again.
+    restarting: bool,
+    attempt:    u32,
+}
+
+/// How the crash monitor answers a server exit.
+#[derive(Debug, PartialEq, Eq)]
+enum ExitResponse {
+    /// We stopped the server ourselves (`kill_server`, window close, restart).
+    Expected,
+    /// Report a crash, and restart as this attempt number when set.
+    Crash { restart_attempt: Option<u32> },
+}
+
+fn exit_response(shutdown_requested: bool, auto_restart: bool, restarts_so_far: u32) -> ExitResponse {
+    if shutdown_requested {
+        return ExitResponse::Expected;
+    }
+    let attempt = restarts_so_far + 1;
+    ExitResponse::Crash { restart_attempt: (auto_restart && attempt <= MAX_CRASH_RESTARTS).then_some(attempt) }
+}
+
+/// Crash monitor: called when the spawned server process exits. Exits we
+/// asked for (`shutdown_requested`) are expected; anything else is
+/// reported as `server-crashed` and `server-exited` and, with auto-restart
+/// on, answered with a restart after an exponential backoff.
+fn handle_server_exit(app: &tauri::AppHandle, pid: u32, code: Option<i32>) {
+    let state = app.state::<ServerState>();
+    let (was_tracked, response) = state.record_exit(pid, load_setting::<bool>("auto_restart").unwrap_or(false));
+    if was_tracked {
+        let _ = std::fs::remove_file(server_pid_file());
+    }
+    let ExitResponse::Crash { restart_attempt } = response else {
+        println!("Server (pid {pid}) stopped as requested.");
+        return;
+    };
+    eprintln!("Server (pid {pid}) exited unexpectedly with code {code:?}");
+    let _ = app.emit("server-crashed", code);
+
+    let attempt = restart_attempt.unwrap_or(state.crash_restarts.load(Ordering::SeqCst) + 1);
+    let _ = app.emit("server-exited", ServerExit { pid, code, restarting: restart_attempt.is_some(), attempt });
+    if restart_attempt.is_none() {
+        return;
+    }
+    state.crash_restarts.store(attempt, Ordering::SeqCst);
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let delay = std::time::Duration::from_secs(1 << (attempt - 1));
+        tokio::time::sleep(delay).await;
+        // The user may have stopped or restarted the server in the meantime.
+        let state = app.state::<ServerState>();
+        if state.shutdown_requested.load(Ordering::SeqCst) || server_is_tracked(&app) {
+            return;
+        }
+        println!("Watchdog restarting server (attempt {attempt}/{MAX_CRASH_RESTARTS})");
+        if let Err(e) = restart_server(&app).await {
+            eprintln!("Watchdog restart failed: {e}");
+        }
+    });
+}
+
+/// Storage engines we may find in the Suwayomi data dir.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DatabaseKind {
+    Sqlite,
+    /// Suwayomi's default embedded H2 store (`database.mv.db`).
+    H2,
+}
+
+/// Locate the server database file. SQLite files are recognised by their
+/// header so a stray `.db` of another format isn't touched.
+fn find_server_database() -> Result<(PathBuf, DatabaseKind), String> {
+    let data_dir = suwayomi_data_dir();
+    for name in ["database.db", "database.sqlite", "tachidesk.db"] {
+        let p = data_dir.join(name);
+        let mut header = [0u8; 16];
+        let is_sqlite = std::fs::File::open(&p)
+            .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut header))
+            .is_ok_and(|_| &header == b"SQLite format 3\0");
+        if is_sqlite {
+            return Ok((p, DatabaseKind::Sqlite));
+        }
+    }
+    let h2 = data_dir.join("database.mv.db");
+    if h2.exists() {
+        return Ok((h2, DatabaseKind::H2));
+    }
+    Err(format!("No server database found in {}", data_dir.display()))
+}
+
+/// Size of a SQLite database including its `-wal` and `-shm` sidecars.
+fn sqlite_footprint(db: &Path) -> u64 {
+    ["", "-wal", "-shm"]
+        .iter()
+        .filter_map(|suffix| {
+            let mut p = db.as_os_str().to_owned();
+            p.push(suffix);
+            std::fs::metadata(PathBuf::from(p)).ok()
+        })
+        .map(|m| m.len())
+        .sum()
+}
+
+fn server_is_tracked(app: &tauri::AppHandle) -> bool {
+    app.state::<ServerState>().lock().is_some()
+}
+
+/// Compact the server database and return the number of bytes reclaimed.
+/// Refuses while any server is running on it, ours or not, since both would
+/// be writing the file.
+#[tauri::command]
+async fn vacuum_server_database(app: tauri::AppHandle) -> Result<u64, String> {
+    if server_is_tracked(&app) {
+        return Err("Stop the server before compacting its database".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(vacuum_idle_database)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn vacuum_idle_database() -> Result<u64, String> {
+    if !own_server_pids().is_empty() || server_port_open() {
+        return Err("A server is still running on this data folder; stop it before compacting its database".to_string());
+    }
+    let (db, kind) = find_server_database()?;
+    if kind == DatabaseKind::H2 {
+        return Err("The server uses an H2 database, which can only be compacted by the server itself".to_string());
+    }
+
+    let before = sqlite_footprint(&db);
+    let conn = rusqlite::Connection::open(&db)
+        .map_err(|e| format!("Could not open database: {e}"))?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")
+        .map_err(|e| format!("VACUUM failed: {e}"))?;
+    drop(conn);
+    let after = sqlite_footprint(&db);
+
+    println!("Vacuumed {db:?}: {before} -> {after} bytes");
+    Ok(before.saturating_sub(after))
+}
+
+#[derive(Serialize, Clone)]
+pub struct DatabaseCheck {
+    ok:     bool,
+    errors: Vec<String>,
+}
+
+/// Prefix of the `spawn_server` error returned when the pre-spawn integrity
+/// check fails, so the UI can offer a restore instead of a generic message.
+const DB_CORRUPT_ERROR: &str = "server-database-corrupt";
+
+/// Holds the PID of the server we spawned; left behind if it (or we) went
+/// down without a clean kill.
+fn server_pid_file() -> PathBuf {
+    suwayomi_data_dir().join("moku-server.pid")
+}
+
+/// Whether a process looks like a Suwayomi server (the launcher script, or a
+/// JVM running the Suwayomi jar).
+fn is_suwayomi_process(p: &sysinfo::Process) -> bool {
+    let mut haystack = p.name().to_string_lossy().to_lowercase();
+    for arg in p.cmd() {
+        haystack.push(' ');
+        haystack.push_str(&arg.to_string_lossy().to_lowercase());
+    }
+    haystack.contains("suwayomi") || haystack.contains("tachidesk")
+}
+
+#[derive(Serialize)]
+pub struct PortHolder {
+    pid:  u32,
+    name: String,
+}
+
+/// PID of the process listening on TCP `port`, from the kernel's socket
+/// table: inode lookup through /proc on Linux, `lsof` on macOS and
+/// `netstat` on Windows.
+#[cfg(target_os = "linux")]
+fn port_holder_pid(port: u16) -> Option<u32> {
+    const LISTEN: &str = "0A";
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|p| std::fs::read_to_string(p).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let cols: Vec<&str> = line.split_whitespace().collect();
+                    let local_port = cols.get(1)?.rsplit_once(':')?.1;
+                    let matches = u16::from_str_radix(local_port, 16).ok()? == port
+                        && *cols.get(3)? == LISTEN;
+                    let inode = cols.get(9)?;
+                    matches.then(|| format!("socket:[{inode}]"))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+    std::fs::read_dir("/proc").ok()?.filter_map(|e| e.ok()).find_map(|proc_entry| {
+        let pid: u32 = proc_entry.file_name().to_str()?.parse().ok()?;
+        let holds = std::fs::read_dir(proc_entry.path().join("fd"))
+            .ok()?
+            .filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+            .any(|target| inodes.iter().any(|i| target.as_os_str() == i.as_str()));
+        holds.then_some(pid)
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn port_holder_pid(port: u16) -> Option<u32> {
+    let out = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{port}"), "-sTCP:LISTEN", "-t"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout).lines().find_map(|l| l.trim().parse().ok())
+}
+
+#[cfg(target_os = "windows")]
+fn port_holder_pid(port: u16) -> Option<u32> {
+    let out = std::process::Command::new("netstat").arg("-ano").output().ok()?;
+    let suffix = format!(":{port}");
+    String::from_utf8_lossy(&out.stdout).lines().find_map(|line| {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let listening = cols.first() == Some(&"TCP")
+            && cols.get(1).is_some_and(|local| local.ends_with(&suffix))
+            && cols.get(3) == Some(&"LISTENING");
+        listening.then(|| cols.get(4)?.parse().ok()).flatten()
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn port_holder_pid(_port: u16) -> Option<u32> {
+    None
+}
+
+fn port_holder(port: u16) -> Option<PortHolder> {
+    let pid = port_holder_pid(port)?;
+    let mut sys = sysinfo::System::new();
+    let spid = sysinfo::Pid::from_u32(pid);
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[spid]), true);
+    let name = sys
+        .process(spid)
+        .map(|p| p.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| "unknown".to_string());
+    Some(PortHolder { pid, name })
+}
+
+/// Identify the process listening on `port`, or `None` if it's free (or
+/// the holder can't be seen, e.g. it belongs to another user on Linux).
+#[tauri::command]
+async fn find_port_holder(port: u16) -> Option<PortHolder> {
+    tauri::async_runtime::spawn_blocking(move || port_holder(port))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Error for a server port that's already taken by something we didn't
+/// adopt, naming the holder when we can find it.
+fn port_in_use_error(port: u16) -> String {
+    match port_holder(port) {
+        Some(PortHolder { pid, name }) => format!("port {port} held by pid {pid} ({name})"),
+        None => format!("port {port} is already in use"),
+    }
+}
+
+/// Whether something accepts TCP connections on the local server port.
+fn server_port_open() -> bool {
+    use std::net::ToSocketAddrs;
+    let (ip, port) = local_server_addr();
+    (ip.as_str(), port)
+        .to_socket_addrs()
+        .into_iter()
+        .flatten()
+        .any(|addr| {
+            std::net::TcpStream::connect_timeout(&addr, std::time::Duration::from_millis(500)).is_ok()
+        })
+}
+
+/// PID from the PID file, if it still names a live Suwayomi process and the
+/// server port answers.
+async fn find_orphaned_server() -> Option<u32> {
+    let pid: u32 = std::fs::read_to_string(server_pid_file()).ok()?.trim().parse().ok()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut sys = sysinfo::System::new();
+        let spid = sysinfo::Pid::from_u32(pid);
+        sys.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::Some(&[spid]),
+            true,
+            sysinfo::ProcessRefreshKind::new()
+                .with_cmd(sysinfo::UpdateKind::Always)
+                .with_exe(sysinfo::UpdateKind::Always),
+        );
+        let alive = sys.process(spid).is_some_and(is_suwayomi_process);
+        (alive && server_port_open()).then_some(pid)
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+fn run_database_check() -> Result<DatabaseCheck, String> {
+    let (db, kind) = find_server_database()?;
+    if kind == DatabaseKind::H2 {
+        // We can't run H2's own checks without a JVM; the best we can do is
+        // make sure the store header survived.
+        let mut header = [0u8; 4];
+        let readable = std::fs::File::open(&db)
+            .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut header))
+            .is_ok();
+        let errors = if readable && &header == b"H:2," {
+            vec![]
+        } else {
+            vec!["H2 store header is missing or unreadable".to_string()]
+        };
+        return Ok(DatabaseCheck { ok: errors.is_empty(), errors });
+    }
+
+    let conn = rusqlite::Connection::open_with_flags(
+        &db,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Could not open database: {e}"))?;
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| e.to_string())?;
+    let rows: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| format!("Integrity check failed to run: {e}"))?;
+    let errors: Vec<String> = rows.into_iter().filter(|r| r != "ok").collect();
+    Ok(DatabaseCheck { ok: errors.is_empty(), errors })
+}
+
+/// Run an integrity check over the server database without modifying it.
+#[tauri::command]
+fn check_server_database() -> Result<DatabaseCheck, String> {
+    run_database_check()
+}
+
+#[derive(Serialize)]
+pub struct FlushResult {
+    /// Whether the on-disk files reached a point safe to copy.
+    consistent: bool,
+    /// `"wal_checkpoint"` for SQLite, `"paused_downloader"` for H2.
+    method:     &'static str,
+    detail:     String,
+}
+
+/// H2 flushes its store on a timer (one second by default); wait a little
+/// longer than that after quiescing the server.
+const H2_WRITE_DELAY_MS: u64 = 2000;
+
+/// Bring the live server's database to a consistent on-disk state for a hot
+/// backup, without restarting it.
+#[tauri::command]
+async fn flush_server_state() -> Result<FlushResult, String> {
+    let (db, kind) = find_server_database()?;
+
+    if kind == DatabaseKind::Sqlite {
+        return tauri::async_runtime::spawn_blocking(move || {
+            let conn = rusqlite::Connection::open(&db).map_err(|e| format!("Could not open database: {e}"))?;
+            conn.busy_timeout(std::time::Duration::from_secs(2)).map_err(|e| e.to_string())?;
+            // PASSIVE never blocks the server's writers; it just reports
+            // whether it managed to copy the whole WAL back.
+            let (busy, log, done): (i64, i64, i64) = conn
+                .query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+                .map_err(|e| format!("Checkpoint failed: {e}"))?;
+            let consistent = busy == 0 && log == done;
+            Ok(FlushResult {
+                consistent,
+                method: "wal_checkpoint",
+                detail: format!("{done} of {log} WAL frames checkpointed"),
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    // H2 has no external checkpoint; pause the downloader (the main source
+    // of writes) long enough for the store to flush, then resume it.
+    let was_running = downloader_running().await.unwrap_or(false);
+    if let Err(e) = local_graphql("mutation { stopDownloader(input: {}) { clientMutationId } }").await {
+        return Ok(FlushResult {
+            consistent: false,
+            method: "paused_downloader",
+            detail: format!("Could not pause the server: {e}"),
+        });
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(H2_WRITE_DELAY_MS)).await;
+    if was_running {
+        let _ = local_graphql("mutation { startDownloader(input: {}) { clientMutationId } }").await;
+    }
+    Ok(FlushResult {
+        consistent: true,
+        method: "paused_downloader",
+        detail: "Downloader paused while the H2 store flushed".to_string(),
+    })
+}
+
+/// True on the very first launch, i.e. no server.conf existed at startup.
+#[tauri::command]
+fn is_first_run(state: tauri::State<FirstRun>) -> bool {
+    state.0
+}
+
+/// Downloaded server releases live here.
+fn servers_dir() -> PathBuf {
+    suwayomi_data_dir().join("servers")
+}
+
+/// Shared between `download_server` and `cancel_server_download`.
+#[derive(Default)]
+struct ServerDownloadState {
+    active:       AtomicBool,
+    cancel:       AtomicBool,
+    keep_partial: AtomicBool,
+}
+
+#[derive(Serialize, Clone)]
+struct ServerDownloadProgress {
+    downloaded: u64,
+    total:      Option<u64>,
+}
+
+#[derive(Serialize, Clone)]
+struct ServerDownloadCancelled {
+    /// The `.part` file left behind for a later resume, if it was kept.
+    partial_path: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct ServerDownloadStarted {
+    /// Continued from an earlier `.part` rather than starting from zero.
+    resumed: bool,
+    offset:  u64,
+}
+
+/// Written next to a `.part` file so an interrupted download can be
+/// resumed on a later launch.
+#[derive(Serialize, serde::Deserialize, Clone)]
+pub struct PartialServerDownload {
+    url:          String,
+    partial_path: String,
+    /// Expected size of the finished file, when the server sent one.
+    total:        Option<u64>,
+}
+
+fn partial_meta_path(part: &Path) -> PathBuf {
+    let mut p = part.as_os_str().to_owned();
+    p.push(".json");
+    PathBuf::from(p)
+}
+
+/// An interrupted server download waiting to be resumed, if any.
+fn find_partial_server_download() -> Option<(PartialServerDownload, u64)> {
+    WalkDir::new(servers_dir()).max_depth(2).into_iter().filter_map(|e| e.ok()).find_map(|entry| {
+        let path = entry.path();
+        let part = path.to_str()?.strip_suffix(".json").filter(|p| p.ends_with(".part"))?;
+        let meta: PartialServerDownload = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+        let downloaded = std::fs::metadata(part).ok()?.len();
+        Some((meta, downloaded))
+    })
+}
+
+#[derive(Serialize, Clone)]
+struct ResumableServerDownload {
+    #[serde(flatten)]
+    meta:       PartialServerDownload,
+    downloaded: u64,
+}
+
+#[derive(Serialize)]
+pub struct DownloadedServer {
+    path:   String,
+    bytes:  u64,
+    sha256: String,
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Stream a server release into `servers/`, verifying its SHA-256 when the
+/// caller knows it. Emits `server-download-progress` as chunks arrive and
+/// checks for cancellation between chunks. A `.part` left by an earlier
+/// attempt at the same URL is resumed; `server-download-started` says which.
+#[tauri::command]
+async fn download_server(
+    url: String,
+    sha256: Option<String>,
+    version: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<DownloadedServer, String> {
+    let version = version.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if let Some(v) = &version {
+        check_version_name(v)?;
+    }
+    let state = app.state::<ServerDownloadState>();
+    if state.active.swap(true, Ordering::SeqCst) {
+        return Err("A server download is already in progress".to_string());
+    }
+    state.cancel.store(false, Ordering::SeqCst);
+    state.keep_partial.store(false, Ordering::SeqCst);
+
+    let result = run_server_download(&app, &url, sha256.as_deref(), version.as_deref()).await;
+    state.active.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn run_server_download(
+    app: &tauri::AppHandle,
+    url: &str,
+    expected_sha256: Option<&str>,
+    version: Option<&str>,
+) -> Result<DownloadedServer, String> {
+    use sha2::Digest;
+    use tokio::io::AsyncWriteExt;
+
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URL: {e}"))?;
+    if parsed.scheme() != "https" {
+        return Err("Server downloads must use https".to_string());
+    }
+    let file_name = parsed
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .filter(|n| !n.is_empty() && !n.starts_with('.'))
+        .ok_or_else(|| "URL does not name a file".to_string())?
+        .to_string();
+
+    let dir = match version {
+        Some(v) => servers_dir().join(v),
+        None => servers_dir(),
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Could not create {}: {e}", dir.display()))?;
+    let dest = dir.join(&file_name);
+    let part = dir.join(format!("{file_name}.part"));
+
+    let meta_path = partial_meta_path(&part);
+
+    // A `.part` left by an earlier run of the same URL is resumed with a
+    // Range request; anything else starts over.
+    let stored = std::fs::read(&meta_path)
+        .ok()
+        .and_then(|b| serde_json::from_slice::<PartialServerDownload>(&b).ok())
+        .filter(|meta| meta.url == url);
+    let mut offset = match &stored {
+        Some(_) => std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+    let stored_total = stored.and_then(|meta| meta.total);
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("Moku/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = client.get(parsed.clone());
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    let mut resp = request.send().await.map_err(|e| format!("Download failed: {e}"))?;
+    // 416 means the range starts at or past the end: either the `.part` is
+    // already whole, or the file changed underneath it and must be refetched.
+    let mut complete = false;
+    if offset > 0 && resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        if stored_total == Some(offset) {
+            complete = true;
+        } else {
+            let _ = std::fs::remove_file(&part);
+            let _ = std::fs::remove_file(&meta_path);
+            offset = 0;
+            resp = client.get(parsed).send().await.map_err(|e| format!("Download failed: {e}"))?;
+        }
+    }
+    let mut resp = if complete {
+        None
+    } else {
+        Some(resp.error_for_status().map_err(|e| format!("Download failed: {e}"))?)
+    };
+    let resumed = complete
+        || (offset > 0 && resp.as_ref().is_some_and(|r| r.status() == reqwest::StatusCode::PARTIAL_CONTENT));
+    let offset = if resumed { offset } else { 0 };
+    let total = match &resp {
+        Some(r) => r.content_length().map(|len| len + offset),
+        None => stored_total,
+    };
+    let _ = app.emit("server-download-started", ServerDownloadStarted { resumed, offset });
+
+    let mut hasher = sha2::Sha256::new();
+    let mut file = if resumed {
+        // The checksum covers the whole file, so replay what's on disk.
+        hash_existing_part(&mut hasher, &part)
+            .await
+            .map_err(|e| format!("Could not read {}: {e}", part.display()))?;
+        tokio::fs::OpenOptions::new().append(true).open(&part).await
+    } else {
+        tokio::fs::File::create(&part).await
+    }
+    .map_err(|e| format!("Could not open {}: {e}", part.display()))?;
+    let meta = PartialServerDownload { url: url.to_string(), partial_path: native_path_string(&part), total };
+    let _ = std::fs::write(&meta_path, serde_json::to_vec(&meta).unwrap_or_default());
+    let mut downloaded = offset;
+    let mut last_emit = std::time::Instant::now();
+    let state = app.state::<ServerDownloadState>();
+
+    while let Some(chunk) = match resp.as_mut() {
+        Some(r) => r.chunk().await.map_err(|e| format!("Download failed: {e}"))?,
+        None => None,
+    } {
+        if state.cancel.load(Ordering::SeqCst) {
+            drop(file);
+            let keep = state.keep_partial.load(Ordering::SeqCst);
+            if !keep {
+                let _ = std::fs::remove_file(&part);
+                let _ = std::fs::remove_file(&meta_path);
+            }
+            let _ = app.emit("server-download-cancelled", ServerDownloadCancelled {
+                partial_path: keep.then(|| native_path_string(&part)),
+            });
+            println!("Server download cancelled at {downloaded} bytes");
+            return Err("Server download cancelled".to_string());
+        }
+        file.write_all(&chunk).await.map_err(|e| format!("Write failed: {e}"))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        if last_emit.elapsed() >= std::time::Duration::from_millis(200) {
+            last_emit = std::time::Instant::now();
+            let _ = app.emit("server-download-progress", ServerDownloadProgress { downloaded, total });
+        }
+    }
+    file.flush().await.map_err(|e| format!("Write failed: {e}"))?;
+    drop(file);
+    let _ = app.emit("server-download-progress", ServerDownloadProgress { downloaded, total });
+
+    let digest = hex_digest(&hasher.finalize());
+    if let Some(expected) = expected_sha256 {
+        if !digest.eq_ignore_ascii_case(expected.trim()) {
+            let _ = std::fs::remove_file(&part);
+            let _ = std::fs::remove_file(&meta_path);
+            return Err(format!("Checksum mismatch: expected {expected}, got {digest}"));
+        }
+    }
+    std::fs::rename(&part, &dest).map_err(|e| format!("Could not finalise download: {e}"))?;
+    let _ = std::fs::remove_file(&meta_path);
+
+    println!("Downloaded server to {dest:?} ({downloaded} bytes)");
+    Ok(DownloadedServer {
+        path:   native_path_string(&dest),
+        bytes:  downloaded,
+        sha256: digest,
+    })
+}
+
+/// Feeds an existing `.part` into `hasher` in fixed-size reads, so resuming a
+/// large server jar doesn't load it into memory.
+async fn hash_existing_part(hasher: &mut sha2::Sha256, part: &Path) -> std::io::Result<()> {
+    use sha2::Digest;
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(part).await?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+/// Version directory names are used as paths, so keep them to one plain
+/// component.
+fn check_version_name(version: &str) -> Result<(), String> {
+    let valid = !version.is_empty()
+        && version != "."
+        && version != ".."
+        && !version.contains(['/', '\\', ':'])
+        && !version.starts_with('.');
+    if valid { Ok(()) } else { Err(format!("Invalid server version name {version:?}")) }
+}
+
+/// The server jar inside a `servers/<version>/` directory.
+fn server_version_jar(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|x| x.eq_ignore_ascii_case("jar")))
+        .min()
+}
+
+/// Version picked with `set_active_server_version`, if it's still installed.
+fn active_server_version() -> Option<String> {
+    load_setting::<String>("active_server_version")
+        .filter(|v| check_version_name(v).is_ok() && servers_dir().join(v).is_dir())
+}
+
+#[derive(Serialize)]
+pub struct ServerVersion {
+    version:        String,
+    jar:            String,
+    bytes:          u64,
+    installed_unix: u64,
+    active:         bool,
+}
+
+/// Server versions installed under `servers/<version>/`, oldest first.
+#[tauri::command]
+fn list_server_versions() -> Vec<ServerVersion> {
+    let active = active_server_version();
+    let mut versions: Vec<ServerVersion> = std::fs::read_dir(servers_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let version = e.file_name().to_str()?.to_string();
+            let jar = server_version_jar(&e.path())?;
+            let meta = std::fs::metadata(&jar).ok()?;
+            Some(ServerVersion {
+                active: active.as_deref() == Some(version.as_str()),
+                jar: native_path_string(&jar),
+                bytes: meta.len(),
+                installed_unix: meta.modified().map(unix_secs).unwrap_or(0),
+                version,
+            })
+        })
+        .collect();
+    versions.sort_by(|a, b| natural_cmp(&a.version, &b.version));
+    versions
+}
+
+#[derive(Serialize)]
+pub struct ActiveServerVersion {
+    version:          String,
+    restart_required: bool,
+    /// Set when switching to an older version, whose schema may predate
+    /// the migrations the current database has already been through.
+    warning:          Option<String>,
+}
+
+/// Make `version` the server that `spawn_server` starts, remembering the
+/// one it replaces for `rollback_server_version`.
+fn activate_server_version(version: &str) -> Result<ActiveServerVersion, String> {
+    check_version_name(version)?;
+    if server_version_jar(&servers_dir().join(version)).is_none() {
+        return Err(format!("Server version {version} is not installed"));
+    }
+    let previous = active_server_version();
+    if previous.as_deref() != Some(version) {
+        store_setting("previous_server_version", previous.clone().into())?;
+    }
+    store_setting("active_server_version", version.into())?;
+    let warning = previous
+        .filter(|p| natural_cmp(version, p) == std::cmp::Ordering::Less)
+        .map(|p| {
+            format!(
+                "{version} is older than {p}. The server migrates its database forward, so an older \
+                 version may not open it; back up the data folder before starting it."
+            )
+        });
+    Ok(ActiveServerVersion { version: version.to_string(), restart_required: true, warning })
+}
+
+#[tauri::command]
+fn set_active_server_version(version: String) -> Result<ActiveServerVersion, String> {
+    activate_server_version(version.trim())
+}
+
+/// Switch back to the version that was active before the last switch and
+/// restart the server on it.
+#[tauri::command]
+async fn rollback_server_version(app: tauri::AppHandle) -> Result<ActiveServerVersion, String> {
+    let current = active_server_version();
+    let previous = load_setting::<String>("previous_server_version")
+        .filter(|v| Some(v) != current.as_ref())
+        .filter(|v| check_version_name(v).is_ok() && server_version_jar(&servers_dir().join(v)).is_some())
+        .ok_or("No previous version to roll back to")?;
+    let result = activate_server_version(&previous)?;
+    if server_is_tracked(&app) {
+        restart_server(&app).await?;
+    }
+    Ok(ActiveServerVersion { restart_required: false, ..result })
+}
+
+/// Remove an installed version. The active one can't be deleted.
+#[tauri::command]
+fn delete_server_version(version: String) -> Result<u64, String> {
+    let version = version.trim();
+    check_version_name(version)?;
+    if active_server_version().as_deref() == Some(version) {
+        return Err(format!("{version} is the active server version; switch to another first"));
+    }
+    let dir = servers_dir().join(version);
+    if !dir.is_dir() {
+        return Err(format!("Server version {version} is not installed"));
+    }
+    let bytes = path_bytes(&dir);
+    std::fs::remove_dir_all(&dir).map_err(|e| format!("Could not delete {}: {e}", dir.display()))?;
+    Ok(bytes)
+}
+
+/// Minisign public keys trusted for server releases, as (identity, base64 key).
+/// Suwayomi doesn't sign its releases yet, so this is empty and verification
+/// falls back to the SHA-256 checksum until a key is published.
+const TRUSTED_SERVER_KEYS: &[(&str, &str)] = &[];
+
+#[derive(Serialize)]
+pub struct SignatureCheck {
+    verified: bool,
+    /// `"signature"` when a trusted key vouched for the file, else `"checksum"`.
+    method:   &'static str,
+    signer:   Option<String>,
+    sha256:   String,
+    detail:   Option<String>,
+}
+
+/// Verify a server binary against a detached minisign signature
+/// (`<path>.minisig` unless given), falling back to comparing its SHA-256
+/// with `sha256` when no signature or trusted key is available.
+#[tauri::command]
+async fn verify_server_signature(
+    path: String,
+    signature_path: Option<String>,
+    sha256: Option<String>,
+) -> Result<SignatureCheck, String> {
+    tauri::async_runtime::spawn_blocking(move || verify_server_file(path, signature_path, sha256))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn verify_server_file(
+    path: String,
+    signature_path: Option<String>,
+    sha256: Option<String>,
+) -> Result<SignatureCheck, String> {
+    use sha2::Digest;
+    use std::io::Read;
+
+    let path = PathBuf::from(path);
+    let sig_path = signature_path
+        .filter(|p| !p.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let mut p = path.as_os_str().to_owned();
+            p.push(".minisig");
+            PathBuf::from(p)
+        });
+    let signature = if sig_path.exists() {
+        Some(
+            minisign_verify::Signature::from_file(&sig_path)
+                .map_err(|e| format!("Could not read signature: {e}"))?,
+        )
+    } else {
+        None
+    };
+    let keys: Vec<(&str, minisign_verify::PublicKey)> = TRUSTED_SERVER_KEYS
+        .iter()
+        .filter_map(|(who, key)| Some((*who, minisign_verify::PublicKey::from_base64(key).ok()?)))
+        .collect();
+    let mut verifiers: Vec<(&str, minisign_verify::StreamVerifier)> = match &signature {
+        Some(sig) => keys
+            .iter()
+            .filter_map(|(who, key)| Some((*who, key.verify_stream(sig).ok()?)))
+            .collect(),
+        None => vec![],
+    };
+
+    // One pass over the (large) jar feeds both the signature and the checksum.
+    let mut file = std::fs::File::open(&path).map_err(|e| format!("Could not open {}: {e}", path.display()))?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        for (_, v) in &mut verifiers {
+            v.update(&buf[..n]);
+        }
+    }
+    let digest = hex_digest(&hasher.finalize());
+
+    if let Some(sig) = &signature {
+        let signer = verifiers
+            .iter_mut()
+            .find_map(|(who, v)| v.finalize().is_ok().then_some(*who));
+        if let Some(who) = signer {
+            return Ok(SignatureCheck {
+                verified: true,
+                method:   "signature",
+                signer:   Some(format!("{who} ({})", sig.trusted_comment())),
+                sha256:   digest,
+                detail:   None,
+            });
+        }
+        if !keys.is_empty() {
+            return Ok(SignatureCheck {
+                verified: false,
+                method:   "signature",
+                signer:   None,
+                sha256:   digest,
+                detail:   Some("Signature does not match any trusted key".to_string()),
+            });
+        }
+    }
+
+    let (verified, mut detail) = match sha256.as_deref().map(str::trim) {
+        Some(expected) if digest.eq_ignore_ascii_case(expected) => (true, None),
+        Some(expected) => (false, Some(format!("Checksum mismatch: expected {expected}"))),
+        None => (false, Some("No signature or checksum available to verify against".to_string())),
+    };
+    if signature.is_some() && keys.is_empty() {
+        let unchecked = "The signature could not be checked because no trusted key is configured";
+        detail = Some(match detail {
+            Some(d) => format!("{unchecked}. {d}"),
+            None => unchecked.to_string(),
+        });
+    }
+    Ok(SignatureCheck { verified, method: "checksum", signer: None, sha256: digest, detail })
 }
 
-/// Returns the true OS-level scale factor for the main window.
-/// On Linux this bypasses WebKitGTK's unreliable devicePixelRatio.
-/// On macOS the value comes directly from the native window.
+/// Ask a running `download_server` to stop after its current chunk.
+/// Returns false when nothing was downloading.
 #[tauri::command]
-fn get_scale_factor(window: tauri::Window) -> f64 {
-    window.scale_factor().unwrap_or(1.0)
-}
-
-fn kill_tachidesk(app: &tauri::AppHandle) {
-    let state = app.state::<ServerState>();
-    let mut guard = state.0.lock().unwrap();
-    if let Some(child) = guard.take() {
-        let _ = child.kill();
-        println!("Killed tracked server child.");
+fn cancel_server_download(keep_partial: bool, state: tauri::State<ServerDownloadState>) -> bool {
+    if !state.active.load(Ordering::SeqCst) {
+        return false;
     }
+    state.keep_partial.store(keep_partial, Ordering::SeqCst);
+    state.cancel.store(true, Ordering::SeqCst);
+    true
+}
 
-    #[cfg(target_os = "windows")]
-    let _ = std::process::Command::new("taskkill")
-        .args(["/F", "/FI", "IMAGENAME eq tachidesk*"])
-        .status();
-
-    #[cfg(not(target_os = "windows"))]
-    let _ = std::process::Command::new("pkill")
-        .arg("-f")
-        .arg("tachidesk")
-        .status();
+#[derive(Serialize)]
+pub struct PingResult {
+    reachable:  bool,
+    /// Median round trip of the successful samples.
+    latency_ms: Option<u64>,
 }
 
-/// The default server.conf we seed on first launch.
-/// Mirrors the Flatpak wrapper: headless, no tray, no browser pop-up.
-const DEFAULT_SERVER_CONF: &str = r#"server.ip = "127.0.0.1"
-server.port = 4567
-server.webUIEnabled = false
-server.initialOpenInBrowserEnabled = false
-server.systemTrayEnabled = false
-server.webUIInterface = "browser"
-server.webUIFlavor = "WebUI"
-server.webUIChannel = "stable"
-server.electronPath = ""
-server.debugLogsEnabled = false
-server.downloadAsCbz = true
-server.autoDownloadNewChapters = false
-server.globalUpdateInterval = 12
-server.maxSourcesInParallel = 6
-server.extensionRepos = []
-"#;
+/// Samples taken by `ping_server`; the median smooths out jitter.
+const PING_SAMPLES: usize = 5;
 
-/// Ensure the Suwayomi data dir and server.conf exist, and that the three
-/// keys that cause GUI/JCEF crashes are always set to safe values.
-/// This mirrors the shell-script logic in the Flatpak's tachidesk-server wrapper.
-fn seed_server_conf(data_dir: &PathBuf) {
-    let conf_path = data_dir.join("server.conf");
+/// Time a trivial GraphQL query against `server_url` (or the local server
+/// from server.conf when omitted).
+#[tauri::command]
+async fn ping_server(server_url: Option<String>) -> Result<PingResult, String> {
+    let base = server_url
+        .filter(|u| !u.trim().is_empty())
+        .unwrap_or_else(local_server_url);
+    let endpoint = format!("{}/api/graphql", base.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(3))
+        .build()
+        .map_err(|e| e.to_string())?;
 
-    if !conf_path.exists() {
-        if let Err(e) = std::fs::create_dir_all(data_dir) {
-            eprintln!("Could not create Suwayomi data dir: {e}");
-            return;
-        }
-        if let Err(e) = std::fs::write(&conf_path, DEFAULT_SERVER_CONF) {
-            eprintln!("Could not write server.conf: {e}");
+    let mut samples = Vec::with_capacity(PING_SAMPLES);
+    for _ in 0..PING_SAMPLES {
+        let started = std::time::Instant::now();
+        let ok = client
+            .post(&endpoint)
+            .header("Content-Type", "application/json")
+            .body(r#"{"query":"{ __typename }"}"#)
+            .send()
+            .await
+            .is_ok_and(|r| r.status().is_success());
+        if ok {
+            samples.push(started.elapsed().as_millis() as u64);
         }
-        return;
     }
+    samples.sort_unstable();
+    Ok(PingResult {
+        reachable:  !samples.is_empty(),
+        latency_ms: samples.get(samples.len() / 2).copied(),
+    })
+}
 
-    // Conf already exists — patch the three critical keys in-place.
-    let Ok(contents) = std::fs::read_to_string(&conf_path) else { return };
+/// Last `max_lines` of the newest file in the server logs folder.
+fn tail_latest_log(max_lines: usize) -> Vec<String> {
+    let newest = std::fs::read_dir(server_logs_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .max_by_key(|e| e.metadata().and_then(|m| m.modified()).ok());
+    let Some(entry) = newest else { return vec![] };
+    let Ok(bytes) = std::fs::read(entry.path()) else { return vec![] };
+    let text = String::from_utf8_lossy(&bytes);
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(max_lines)..]
+        .iter()
+        .map(|l| l.to_string())
+        .collect()
+}
 
-    let patched = patch_conf_key(
-        patch_conf_key(
-            patch_conf_key(
-                contents,
-                "server.webUIEnabled",
-                "false",
-            ),
-            "server.initialOpenInBrowserEnabled",
-            "false",
-        ),
-        "server.systemTrayEnabled",
-        "false",
-    );
+/// `$JAVA_HOME/bin/java` when set, else `java` from PATH.
+fn java_binary() -> std::ffi::OsString {
+    std::env::var_os("JAVA_HOME")
+        .map(|home| PathBuf::from(home).join("bin").join("java"))
+        .filter(|p| p.exists() || p.with_extension("exe").exists())
+        .map(PathBuf::into_os_string)
+        .unwrap_or_else(|| "java".into())
+}
 
-    let _ = std::fs::write(&conf_path, patched);
+/// Oldest Java major version current Suwayomi releases run on.
+const MIN_JAVA_MAJOR: u32 = 21;
+
+#[derive(Serialize)]
+pub struct JavaInfo {
+    path:    String,
+    /// First line of `java -version`.
+    version: String,
+    major:   u32,
+    /// `"bundled"`, `"java_home"` or `"path"`.
+    source:  &'static str,
 }
 
-/// Replace `key = <value>` in a HOCON/properties-style conf, or append it
-/// if the key is absent.
-fn patch_conf_key(mut text: String, key: &str, value: &str) -> String {
-    let replacement = format!("{key} = {value}");
-    // Find a line that starts with the key (tolerant of surrounding whitespace)
-    if let Some(pos) = text.lines().position(|l| l.trim_start().starts_with(key)) {
-        let lines: Vec<&str> = text.lines().collect();
-        // We need an owned replacement; rebuild from scratch.
-        let owned: Vec<String> = lines
-            .iter()
-            .enumerate()
-            .map(|(i, l)| {
-                if i == pos { replacement.clone() } else { l.to_string() }
-            })
-            .collect();
-        return owned.join("\n");
+/// Major version from a `java -version` line: `"1.8.0_392"` is 8,
+/// `"21.0.2"` and `"17"` are 21 and 17.
+fn parse_java_major(line: &str) -> Option<u32> {
+    let quoted = line.split('"').nth(1)?;
+    let mut parts = quoted.split(['.', '_', '-', '+']);
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
     }
-    // Key absent — append.
-    if !text.ends_with('\n') { text.push('\n'); }
-    text.push_str(&replacement);
-    text.push('\n');
-    text
 }
 
-/// Resolve the Suwayomi data directory.
-///
-/// - Linux:  $XDG_DATA_HOME/moku/tachidesk  (matches Flatpak path)
-/// - macOS:  ~/Library/Application Support/dev.moku.app/tachidesk
-fn suwayomi_data_dir() -> PathBuf {
-    #[cfg(target_os = "macos")]
-    {
-        dirs::data_dir()
-            .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| PathBuf::from("~")))
-            .join("dev.moku.app/tachidesk")
+/// Find a Java runtime new enough for the server: a JRE bundled in the
+/// resource dir first, then `$JAVA_HOME`, then `java` on PATH. Starts a JVM
+/// per candidate, so keep it off the main thread.
+fn find_java(app: &tauri::AppHandle) -> Result<JavaInfo, String> {
+    let exe = if cfg!(target_os = "windows") { "java.exe" } else { "java" };
+    let mut candidates: Vec<(PathBuf, &'static str)> = vec![];
+    if let Ok(resources) = app.path().resource_dir() {
+        for jre in ["jre", "suwayomi-bundle/jre"] {
+            let java = resources.join(jre).join("bin").join(exe);
+            if java.is_file() {
+                candidates.push((java, "bundled"));
+            }
+        }
     }
-    #[cfg(not(target_os = "macos"))]
-    {
-        let base = std::env::var("XDG_DATA_HOME")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                dirs::data_dir().unwrap_or_else(|| PathBuf::from("/tmp"))
-            });
-        base.join("moku/tachidesk")
+    if let Some(home) = std::env::var_os("JAVA_HOME") {
+        let java = PathBuf::from(home).join("bin").join(exe);
+        if java.is_file() {
+            candidates.push((java, "java_home"));
+        }
+    }
+    candidates.push((PathBuf::from("java"), "path"));
+
+    let mut too_old = vec![];
+    for (path, source) in candidates {
+        let Ok(out) = std::process::Command::new(&path).arg("-version").output() else { continue };
+        let text = String::from_utf8_lossy(&out.stderr);
+        let version = text.lines().next().unwrap_or_default().trim().to_string();
+        let Some(major) = parse_java_major(&version) else { continue };
+        if major >= MIN_JAVA_MAJOR {
+            return Ok(JavaInfo { path: native_path_string(&path), version, major, source });
+        }
+        too_old.push(format!("{} (Java {major})", path.display()));
+    }
+    if too_old.is_empty() {
+        Err(format!("{JAVA_MISSING_ERROR}: no Java runtime found. Install Java {MIN_JAVA_MAJOR} or newer, or set JAVA_HOME."))
+    } else {
+        Err(format!(
+            "{JAVA_MISSING_ERROR}: the server needs Java {MIN_JAVA_MAJOR} or newer, found only {}",
+            too_old.join(", ")
+        ))
     }
 }
 
-/// Everything needed to spawn the server process.
-struct ServerInvocation {
-    /// Path to the executable (javaw.exe on Windows, the sidecar script on macOS/Linux).
-    bin: std::ffi::OsString,
-    /// Extra args prepended before the Suwayomi rootDir flag.
-    /// On Windows: ["-jar", "<path-to-jar>"]
-    /// Elsewhere: []
-    prefix_args: Vec<String>,
-    /// Working directory for the child process.
-    /// On Windows this must be the bundle folder so javaw can find the JRE and jar.
-    /// Elsewhere: None (inherit).
-    working_dir: Option<PathBuf>,
+#[tauri::command]
+async fn check_java(app: tauri::AppHandle) -> Result<JavaInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || find_java(&app))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
-/// Resolve the server binary path.
-///
-/// If the frontend passes a non-empty `binary` string (user override in
-/// Settings) we always use that — on Linux this is the nixpkgs/Flatpak path.
-///
-/// Otherwise we look for the Tauri-bundled sidecar inside the resource dir
-/// and, on Windows, build the javaw + jar invocation from the suwayomi-bundle.
-fn resolve_server_binary(
-    binary: &str,
-    app: &tauri::AppHandle,
-) -> Result<ServerInvocation, String> {
-    if !binary.trim().is_empty() {
-        return Ok(ServerInvocation {
-            bin: std::ffi::OsString::from(binary),
-            prefix_args: vec![],
-            working_dir: None,
-        });
-    }
+/// Ask a running server for its version via GraphQL.
+async fn query_server_about() -> Option<serde_json::Value> {
+    local_graphql("{ aboutServer { name version buildType buildTime } }")
+        .await
+        .ok()?
+        .get("aboutServer")
+        .cloned()
+}
 
-    let resource_dir = app
-        .path()
-        .resource_dir()
-        .map_err(|e| format!("Could not locate resource dir: {e}"))?;
+#[derive(Serialize, Clone)]
+pub struct ServerVersionInfo {
+    /// `"unknown"` when the server is stopped and nothing on disk says.
+    version: String,
+    /// `"stable"`/`"preview"` when the running server reported it.
+    build:   Option<String>,
+    binary:  String,
+    /// `"override"` (path set in Settings), `"server_version"` (one picked
+    /// in Settings) or `"bundled"`.
+    source:  &'static str,
+}
 
-    // ── Windows: invoke the bundled javaw.exe with -jar Suwayomi-Launcher.jar ──
-    #[cfg(target_os = "windows")]
-    {
-        let sidecar = resource_dir.join("suwayomi-server-x86_64-pc-windows-msvc.exe");
-        let bundle_dir = resource_dir.join("suwayomi-bundle");
-        let jar = bundle_dir.join("Suwayomi-Launcher.jar");
+/// Keyed by the resolved command line, so picking another binary or server
+/// version reads the new one.
+type ServerVersionCache = Option<((std::ffi::OsString, Vec<std::ffi::OsString>), ServerVersionInfo)>;
+static SERVER_VERSION_CACHE: Mutex<ServerVersionCache> = Mutex::new(None);
 
-        if sidecar.exists() && jar.exists() {
-            return Ok(ServerInvocation {
-                bin: sidecar.into_os_string(),
-                prefix_args: vec![
-                    "-jar".to_string(),
-                    jar.to_string_lossy().into_owned(),
-                ],
-                working_dir: Some(bundle_dir),
-            });
-        }
-    }
+/// `Implementation-Version` from a jar's manifest.
+fn jar_manifest_version(jar: &Path) -> Option<String> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(jar).ok()?).ok()?;
+    let mut manifest = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("META-INF/MANIFEST.MF").ok()?, &mut manifest).ok()?;
+    manifest
+        .lines()
+        .find_map(|l| l.strip_prefix("Implementation-Version:"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
 
-    // ── macOS / Linux: sidecar script is self-contained ──
-    let candidates = [
-        "suwayomi-server-aarch64-apple-darwin",
-        "suwayomi-server-x86_64-apple-darwin",
-        // plain name as a dev/Linux fallback
-        "suwayomi-server",
-    ];
+/// The jar a launcher script runs, read for its manifest version: a `.jar`
+/// path named in the script, else the release layout's
+/// `bin/Suwayomi-Server.jar`. Both are looked up next to the script and in
+/// the `suwayomi-bundle` shipped beside it.
+fn launched_jar_version(script: &Path) -> Option<String> {
+    let dir = script.parent()?;
+    let roots = [dir.to_path_buf(), dir.join("suwayomi-bundle")];
+    let text = std::fs::read(script)
+        .ok()
+        .filter(|bytes| bytes.len() < 1_000_000)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+    // `"$DIR/bin/Server.jar"` → `bin/Server.jar`: whatever follows the last
+    // shell expansion is relative to the script's folder.
+    let named = text
+        .split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '='))
+        .filter(|token| token.ends_with(".jar"))
+        .map(|token| {
+            let parts: Vec<&str> = token.split('/').collect();
+            match parts.iter().rposition(|p| p.contains(['$', '`', ')', '}'])) {
+                Some(i) => parts[i + 1..].iter().collect::<PathBuf>(),
+                None => PathBuf::from(token),
+            }
+        });
+    let known = std::iter::once(["bin", "Suwayomi-Server.jar"].iter().collect::<PathBuf>());
+    named
+        .chain(known)
+        .flat_map(|rel| roots.iter().map(move |root| root.join(&rel)))
+        .find_map(|jar| jar_manifest_version(&jar))
+}
 
-    for name in &candidates {
-        let p = resource_dir.join(name);
-        if p.exists() {
-            return Ok(ServerInvocation {
-                bin: p.into_os_string(),
-                prefix_args: vec![],
-                working_dir: None,
-            });
+/// The last version a running server reported, so a stopped one whose
+/// binary has no readable manifest can still be named.
+#[derive(Serialize, serde::Deserialize)]
+struct LastServerAbout {
+    binary:  String,
+    version: String,
+    build:   Option<String>,
+}
+
+/// Which Suwayomi server `spawn_server` runs (or would run) and its version.
+/// Asks the running server when it's up, otherwise reads the jar's manifest
+/// (or the jar a launcher script runs) rather than starting a JVM, then the
+/// version the server last reported. Cached until the resolved binary
+/// changes.
+#[tauri::command]
+async fn get_server_version(app: tauri::AppHandle, binary: Option<String>) -> Result<ServerVersionInfo, String> {
+    let binary = binary.or_else(|| lock_unpoisoned(&LAST_SERVER_BINARY).clone()).unwrap_or_default();
+    let invocation = resolve_server_binary(&binary, &app)?;
+    let key = (invocation.bin.clone(), invocation.prefix_args.clone());
+    if let Some((cached_key, version)) = lock_unpoisoned(&SERVER_VERSION_CACHE).as_ref() {
+        if *cached_key == key {
+            return Ok(version.clone());
         }
     }
 
-    Err("Suwayomi server binary not found. Please set the path in Settings.".to_string())
+    let source = if !binary.trim().is_empty() {
+        "override"
+    } else if invocation.java == JavaRuntime::Executable {
+        "server_version"
+    } else {
+        "bundled"
+    };
+    let jar = invocation
+        .prefix_args
+        .iter()
+        .position(|a| a == "-jar")
+        .and_then(|i| invocation.prefix_args.get(i + 1))
+        .map(PathBuf::from);
+    let bin_path = native_path_string(Path::new(&invocation.bin));
+    let about = if server_is_tracked(&app) { query_server_about().await } else { None };
+    let (version, build) = match about {
+        Some(about) => {
+            let version = about["version"].as_str().unwrap_or_default().to_string();
+            let build = about["buildType"].as_str().map(str::to_string);
+            let last = LastServerAbout { binary: bin_path.clone(), version: version.clone(), build: build.clone() };
+            let _ = store_setting("last_server_about", serde_json::to_value(last).unwrap_or_default());
+            (version, build)
+        }
+        None => {
+            let manifest = match &jar {
+                Some(jar) => jar_manifest_version(jar),
+                None => launched_jar_version(Path::new(&invocation.bin)),
+            }
+            .or_else(|| (source == "server_version").then(active_server_version).flatten());
+            let last = || load_setting::<LastServerAbout>("last_server_about").filter(|last| last.binary == bin_path);
+            match manifest {
+                Some(version) => (version, None),
+                None => match last() {
+                    Some(last) => (last.version, last.build),
+                    // Not cached, so the next call after a start asks the server.
+                    None => {
+                        let version = "unknown".to_string();
+                        return Ok(ServerVersionInfo { version, build: None, binary: bin_path, source });
+                    }
+                },
+            }
+        }
+    };
+
+    let version = ServerVersionInfo { version, build, binary: bin_path, source };
+    *lock_unpoisoned(&SERVER_VERSION_CACHE) = Some((key, version.clone()));
+    Ok(version)
+}
+
+const REDACTED: &str = "<redacted>";
+
+/// Conf keys whose values must not end up in a shared bug report:
+/// credentials and anything token-like.
+fn is_secret_conf_key(key: &str) -> bool {
+    let name = key.rsplit('.').next().unwrap_or(key).to_ascii_lowercase();
+    ["password", "username", "user", "token", "secret", "apikey", "credential"]
+        .iter()
+        .any(|marker| name.contains(marker))
 }
 
+/// Gather everything a maintainer asks for in a bug report into one JSON
+/// blob, optionally also writing it to `output_path`.
 #[tauri::command]
-fn spawn_server(binary: String, app: tauri::AppHandle) -> Result<(), String> {
-    let state = app.state::<ServerState>();
-    {
-        let guard = state.0.lock().unwrap();
-        if guard.is_some() {
-            println!("Server already running, skipping spawn.");
-            return Ok(());
-        }
-    }
+async fn dump_state(
+    downloads_path: String,
+    output_path: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<serde_json::Value, String> {
+    use serde_json::json;
+
+    let pid = app
+        .state::<ServerState>()
+        .lock()
+        .as_ref()
+        .map(TrackedServer::pid);
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    let uptime_secs = pid
+        .and_then(|p| sys.process(sysinfo::Pid::from_u32(p)))
+        .map(|p| {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            now.saturating_sub(p.start_time())
+        });
 
-    // Seed server.conf before launching so Suwayomi starts in headless mode.
     let data_dir = suwayomi_data_dir();
-    seed_server_conf(&data_dir);
+    let conf_text = std::fs::read_to_string(data_dir.join("server.conf")).unwrap_or_default();
+    let mut conf = parse_conf(&conf_text);
+    for (key, value) in conf.iter_mut() {
+        if is_secret_conf_key(key) && !value.is_empty() {
+            *value = REDACTED.to_string();
+        }
+    }
+    let defaults = parse_conf(DEFAULT_SERVER_CONF);
+    let changed: serde_json::Map<String, serde_json::Value> = conf
+        .iter()
+        .filter(|(k, v)| defaults.get(*k) != Some(*v))
+        .map(|(k, v)| (k.clone(), json!({ "value": v, "default": defaults.get(k) })))
+        .collect();
+    let missing: Vec<&String> = defaults.keys().filter(|k| !conf.contains_key(*k)).collect();
 
-    let invocation = resolve_server_binary(&binary, &app)?;
-    let shell = app.shell();
+    let storage = storage_info_blocking(&downloads_path)
+        .ok()
+        .and_then(|s| serde_json::to_value(s).ok());
 
-    let rootdir_flag = format!(
-        "-Dsuwayomi.tachidesk.config.server.rootDir={}",
-        data_dir.to_string_lossy()
-    );
+    // The runtime spawn_server would actually use, bundled JRE included.
+    let handle = app.clone();
+    let java = match tauri::async_runtime::spawn_blocking(move || find_java(&handle)).await {
+        Ok(Ok(info)) => json!(info),
+        Ok(Err(e)) => json!({ "error": e }),
+        Err(e) => json!({ "error": e.to_string() }),
+    };
 
-    // Build the full arg list: prefix_args (e.g. -jar foo.jar) + rootDir flag.
-    let args: Vec<String> = invocation.prefix_args.into_iter().chain(std::iter::once(rootdir_flag)).collect();
+    let state = json!({
+        "moku_version": env!("CARGO_PKG_VERSION"),
+        "os": {
+            "name":    sysinfo::System::name(),
+            "version": sysinfo::System::os_version(),
+            "kernel":  sysinfo::System::kernel_version(),
+            "arch":    std::env::consts::ARCH,
+        },
+        "server": {
+            "running":     pid.is_some(),
+            "pid":         pid,
+            "uptime_secs": uptime_secs,
+            "about":       query_server_about().await,
+        },
+        "java": java,
+        "paths": {
+            "data_dir":  native_path_string(&data_dir),
+            "downloads": native_path_string(&resolve_downloads_path(&downloads_path)),
+            "logs":      native_path_string(&server_logs_dir()),
+        },
+        "server_conf": conf,
+        "server_conf_diff": { "changed": changed, "missing": missing },
+        "storage": storage,
+        "log_tail": tail_latest_log(200),
+    });
 
-    // On Windows, set the working directory to the bundle folder so javaw.exe
-    // can resolve the JRE and jar relative paths correctly.
-    let cmd = shell
-        .command(&invocation.bin)
-        .env("JAVA_TOOL_OPTIONS", "-Djava.awt.headless=true")
-        .args(&args)
-        .current_dir(invocation.working_dir.unwrap_or_else(|| std::env::current_dir().unwrap_or_default()));
-
-    match cmd.spawn() {
-        Ok((_rx, child)) => {
-            println!("Spawned server: {:?}", invocation.bin);
-            let mut guard = state.0.lock().unwrap();
-            *guard = Some(child);
-            Ok(())
-        }
-        Err(e) => {
-            eprintln!("Failed to spawn {:?}: {}", invocation.bin, e);
-            Err(e.to_string())
-        }
+    if let Some(out) = output_path.filter(|p| !p.trim().is_empty()) {
+        let pretty = serde_json::to_string_pretty(&state).map_err(|e| e.to_string())?;
+        std::fs::write(&out, pretty).map_err(|e| format!("Could not write {out}: {e}"))?;
     }
+    Ok(state)
 }
 
-
 #[tauri::command]
-fn kill_server(app: tauri::AppHandle) -> Result<(), String> {
-    kill_tachidesk(&app);
-    Ok(())
+async fn kill_server(app: tauri::AppHandle) -> Result<KillReport, String> {
+    tauri::async_runtime::spawn_blocking(move || kill_tachidesk(&app))
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .manage(ServerState(Mutex::new(None)))
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .manage(ServerState {
+            server:             Mutex::new(None),
+            shutdown_requested: AtomicBool::new(false),
+            crash_restarts:     Default::default(),
+            spawning:           AtomicBool::new(false),
+        })
+        .manage(ServerDownloadState::default())
+        .manage(DownloadsWatcher(Mutex::new(None)))
+        .manage(IdleScanState::default())
+        .manage(MeteredPauseState::default())
+        .manage(DownloadRelay::default())
+        .manage(VerifyLibraryState::default())
+        .manage(ServerLogBuffer::default())
+        .manage(StorageScanState::default())
+        .manage(LowDiskState::default())
         .invoke_handler(tauri::generate_handler![
             get_storage_info,
             spawn_server,
             kill_server,
             get_scale_factor,
+            copy_path_to_clipboard,
+            open_url,
+            open_logs_folder,
+            is_first_run,
+            get_data_dir_status,
+            link_downloads_to,
+            get_downloads_path,
+            validate_downloads_path,
+            vacuum_server_database,
+            check_server_database,
+            list_backups,
+            open_backup_folder,
+            download_server,
+            cancel_server_download,
+            verify_server_signature,
+            set_bind_address,
+            ping_server,
+            path_is_case_sensitive,
+            list_network_interfaces,
+            dump_state,
+            watch_downloads,
+            unwatch_downloads,
+            get_manga_storage,
+            get_source_storage,
+            estimate_download_space,
+            verify_cbz,
+            extract_cbz,
+            classify_chapters,
+            repair_cbz,
+            set_idle_scan,
+            set_idle_scan_threshold,
+            is_connection_metered,
+            set_pause_on_metered,
+            flush_server_state,
+            search_logs,
+            set_server_conf_key,
+            set_debug_logging,
+            find_port_holder,
+            set_server_port,
+            get_server_endpoints,
+            get_runtime_environment,
+            clean_orphaned_downloads,
+            delete_manga_folders,
+            remove_duplicate_chapters,
+            set_update_interval,
+            set_auto_download,
+            set_download_as_cbz,
+            set_max_parallel_sources,
+            count_library,
+            set_always_on_top,
+            get_always_on_top,
+            set_fullscreen,
+            toggle_fullscreen,
+            set_window_title,
+            reset_window_title,
+            get_system_theme,
+            center_window,
+            get_primary_monitor_refresh_rate,
+            set_window_opacity,
+            reset_window,
+            start_download_relay,
+            stop_download_relay,
+            verify_library,
+            cancel_verify_library,
+            list_server_versions,
+            set_active_server_version,
+            delete_server_version,
+            rollback_server_version,
+            open_data_dir,
+            set_min_free_disk_space,
+            set_readiness_probe,
+            export_server_conf,
+            import_server_conf,
+            open_path,
+            get_server_status,
+            wait_for_server,
+            get_recent_logs,
+            set_shutdown_timeout,
+            get_server_conf,
+            set_server_conf,
+            cancel_storage_scan,
+            get_storage_breakdown,
+            set_auto_restart,
+            migrate_data_dir,
+            export_backup,
+            import_backup,
+            check_java,
+            set_low_disk_threshold,
+            delete_manga_folder,
+            get_server_version,
         ])
-        .setup(|_app| Ok(()))
-        .on_window_event(|window, event| {
-            if let WindowEvent::Destroyed = event {
-                kill_tachidesk(window.app_handle());
+        .setup(|app| {
+            let handle = app.handle();
+            emit_setup_progress(handle, "creating_data_dir", "Creating data dir");
+            let status = ensure_writable_data_dir();
+            if status.warning.is_some() {
+                let _ = handle.emit("data-dir-warning", status.clone());
             }
+            app.manage(status);
+
+            // Probing only creates the directory, so server.conf is still
+            // untouched at this point.
+            let data_dir = suwayomi_data_dir();
+            app.manage(FirstRun(!data_dir.join("server.conf").exists()));
+
+            emit_setup_progress(handle, "seeding_config", "Seeding config");
+            seed_server_conf(&data_dir);
+
+            if let Some(window) = app.get_webview_window("main") {
+                restore_window_state(&window.as_ref().window());
+                if load_setting::<bool>("always_on_top").unwrap_or(false) {
+                    let _ = window.set_always_on_top(true);
+                }
+                if let Some(opacity) = load_setting::<f64>("window_opacity").filter(|o| *o < 1.0) {
+                    let _ = apply_window_opacity(&window, opacity.clamp(MIN_WINDOW_OPACITY, 1.0));
+                }
+            }
+
+            // Offer to pick up a server download the last session didn't finish;
+            // calling download_server with the same URL resumes it.
+            if let Some((meta, downloaded)) = find_partial_server_download() {
+                let _ = handle.emit("server-download-resumable", ResumableServerDownload { meta, downloaded });
+            }
+
+            start_theme_watch(handle.clone());
+            start_idle_scan_scheduler(handle.clone());
+            start_metered_watch(handle.clone());
+            start_low_disk_watch(handle.clone());
+            Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running moku");
-}
\ No newline at end of file
+        .on_window_event(|window, event| match event {
+            WindowEvent::Moved(_) => {
+                track_window_geometry(window);
+                track_monitor_change(window);
+                // Not every platform sends ScaleFactorChanged when the window
+                // lands on a monitor with a different DPI.
+                emit_scale_factor(window, window_scale_factor(window));
+            }
+            WindowEvent::Resized(_) => {
+                track_window_geometry(window);
+                sync_fullscreen_state(window);
+            }
+            WindowEvent::CloseRequested { .. } => save_window_state(window),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => emit_scale_factor(window, *scale_factor),
+            WindowEvent::ThemeChanged(theme) => {
+                let theme = if *theme == tauri::Theme::Light { "light" } else { "dark" };
+                emit_theme_if_changed(window.app_handle(), theme);
+            }
+            _ => {}
+        })
+        .build(tauri::generate_context!())
+        .expect("error while running moku")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
+                stop_server_before_exit(app, &api);
+            }
+        });
+}
+
+/// Set once the exit-time shutdown has started, and once it has finished.
+static EXIT_SHUTDOWN_STARTED: AtomicBool = AtomicBool::new(false);
+static EXIT_SHUTDOWN_DONE: AtomicBool = AtomicBool::new(false);
+
+/// Hold the app open while the server shuts down, then exit. The graceful
+/// wait can take the whole shutdown timeout, so it runs on its own thread
+/// rather than freezing the event loop.
+fn stop_server_before_exit(app: &tauri::AppHandle, api: &tauri::ExitRequestApi) {
+    if EXIT_SHUTDOWN_DONE.load(Ordering::SeqCst) {
+        return;
+    }
+    api.prevent_exit();
+    if EXIT_SHUTDOWN_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let _ = kill_tachidesk(&app);
+        EXIT_SHUTDOWN_DONE.store(true, Ordering::SeqCst);
+        app.exit(0);
+    });
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_conf_key_replaces_quoted_value() {
+        let text = "server.webUIFlavor = \"WebUI\"\nserver.port = 4567\n".to_string();
+        let patched = patch_conf_key(text, "server.webUIFlavor", "\"Custom\"");
+        assert_eq!(patched, "server.webUIFlavor = \"Custom\"\nserver.port = 4567\n");
+    }
+
+    #[test]
+    fn patch_conf_key_skips_commented_keys() {
+        let text = "# server.port = 1234\n// server.port = 2345\nserver.port = 4567\n".to_string();
+        let patched = patch_conf_key(text, "server.port", "5000");
+        assert_eq!(patched, "# server.port = 1234\n// server.port = 2345\nserver.port = 5000\n");
+    }
+
+    #[test]
+    fn patch_conf_key_appends_when_only_commented() {
+        let text = "# server.port = 1234\n".to_string();
+        let patched = patch_conf_key(text, "server.port", "5000");
+        assert_eq!(patched, "# server.port = 1234\nserver.port = 5000\n");
+    }
+
+    #[test]
+    fn patch_conf_key_tolerates_surrounding_whitespace() {
+        let text = "   server.port   =   4567   \nserver.ip = \"127.0.0.1\"".to_string();
+        let patched = patch_conf_key(text, "server.port", "5000");
+        assert_eq!(patched, "server.port = 5000\nserver.ip = \"127.0.0.1\"");
+    }
+
+    #[test]
+    fn patch_conf_key_ignores_keys_sharing_a_prefix() {
+        let text = "server.portRange = 10\nserver.port = 4567\n".to_string();
+        let patched = patch_conf_key(text, "server.port", "5000");
+        assert_eq!(patched, "server.portRange = 10\nserver.port = 5000\n");
+
+        let text = "server.port = 4567\n".to_string();
+        let patched = patch_conf_key(text, "server.portRange", "10");
+        assert_eq!(patched, "server.port = 4567\nserver.portRange = 10\n");
+    }
+
+    #[test]
+    fn patch_conf_key_updates_only_last_occurrence() {
+        let text = "server.port = 1111\nserver.ip = \"0.0.0.0\"\nserver.port = 2222\n# server.port = 3333\n".to_string();
+        let patched = patch_conf_key(text, "server.port", "5000");
+        assert_eq!(
+            patched,
+            "server.port = 1111\nserver.ip = \"0.0.0.0\"\nserver.port = 5000\n# server.port = 3333\n"
+        );
+    }
+
+    #[test]
+    fn get_server_conf_output_round_trips_through_set() {
+        let conf = parse_conf(DEFAULT_SERVER_CONF);
+        let mut text = DEFAULT_SERVER_CONF.to_string();
+        for (key, value) in &conf {
+            let literal = set_conf_literal(key, value).unwrap_or_else(|e| panic!("{key} = {value}: {e}"));
+            match literal {
+                Some(literal) => text = patch_conf_key(text, key, &literal),
+                None => assert!(GUI_SAFE_CONF_KEYS.iter().any(|(k, _)| k == key)),
+            }
+        }
+        assert_eq!(parse_conf(&text), conf);
+    }
+
+    #[test]
+    fn set_conf_literal_quotes_bare_strings_and_guards_safety_keys() {
+        assert_eq!(set_conf_literal("server.ip", "0.0.0.0"), Ok(Some("\"0.0.0.0\"".to_string())));
+        assert_eq!(set_conf_literal("server.port", "4568"), Ok(Some("4568".to_string())));
+        assert_eq!(set_conf_literal("server.webUIFlavor", "a\nb = c"), Ok(Some("\"a\\nb = c\"".to_string())));
+        assert_eq!(set_conf_literal("server.webUIEnabled", "false"), Ok(None));
+        assert!(set_conf_literal("server.webUIEnabled", "true").is_err());
+        assert!(set_conf_literal("server.extensionRepos", "{}").is_err());
+    }
+
+    #[test]
+    fn java_property_arg_keeps_spaces_and_unicode_in_one_arg() {
+        let dir: PathBuf = ["John Smith", "Médiathèque", "漫画 データ"].iter().collect();
+        let arg = java_property_arg("suwayomi.tachidesk.config.server.rootDir", &dir);
+        let mut expected = std::ffi::OsString::from("-Dsuwayomi.tachidesk.config.server.rootDir=");
+        expected.push(dir.as_os_str());
+        assert_eq!(arg, expected);
+        assert!(arg.to_str().is_some_and(|a| a.ends_with("漫画 データ")));
+    }
+
+    #[test]
+    fn java_property_arg_drops_trailing_separator() {
+        let sep = std::path::MAIN_SEPARATOR;
+        let arg = java_property_arg("rootDir", Path::new(&format!("moku dir{sep}data{sep}")));
+        assert_eq!(arg, std::ffi::OsString::from(format!("-DrootDir=moku dir{sep}data")));
+    }
+
+    #[test]
+    fn server_state_lock_recovers_from_poisoning() {
+        let state = adopted_server_state(42);
+        std::thread::scope(|scope| {
+            let poisoner = scope.spawn(|| {
+                let _guard = state.server.lock().unwrap();
+                panic!("poison the server mutex");
+            });
+            assert!(poisoner.join().is_err());
+        });
+        assert!(state.server.is_poisoned());
+
+        assert_eq!(state.lock().as_ref().map(TrackedServer::pid), Some(42));
+        *state.lock() = Some(TrackedServer::Adopted(7));
+        assert_eq!(state.lock().take().map(|s| s.pid()), Some(7));
+        assert!(state.lock().is_none());
+    }
+
+    #[test]
+    fn requested_shutdown_never_restarts() {
+        assert_eq!(exit_response(true, true, 0), ExitResponse::Expected);
+        assert_eq!(exit_response(true, false, 0), ExitResponse::Expected);
+        assert_eq!(exit_response(true, true, MAX_CRASH_RESTARTS), ExitResponse::Expected);
+    }
+
+    fn adopted_server_state(pid: u32) -> ServerState {
+        ServerState {
+            server:             Mutex::new(Some(TrackedServer::Adopted(pid))),
+            shutdown_requested: AtomicBool::new(false),
+            crash_restarts:     Default::default(),
+            spawning:           AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn exit_after_begin_shutdown_is_expected() {
+        let state = adopted_server_state(42);
+        let tracked = state.begin_shutdown();
+        assert_eq!(tracked.map(|s| s.pid()), Some(42));
+        assert!(state.lock().is_none());
+
+        // The Terminated event for the signalled child arrives afterwards.
+        assert_eq!(state.record_exit(42, true), (false, ExitResponse::Expected));
+    }
+
+    #[test]
+    fn unrequested_exit_clears_tracking_and_counts_as_crash() {
+        let state = adopted_server_state(42);
+        assert_eq!(state.record_exit(7, true), (false, ExitResponse::Crash { restart_attempt: Some(1) }));
+        assert_eq!(state.lock().as_ref().map(TrackedServer::pid), Some(42));
+
+        assert_eq!(state.record_exit(42, true), (true, ExitResponse::Crash { restart_attempt: Some(1) }));
+        assert!(state.lock().is_none());
+    }
+
+    #[test]
+    fn crash_restarts_only_when_enabled_and_under_limit() {
+        assert_eq!(exit_response(false, true, 0), ExitResponse::Crash { restart_attempt: Some(1) });
+        assert_eq!(exit_response(false, false, 0), ExitResponse::Crash { restart_attempt: None });
+        assert_eq!(
+            exit_response(false, true, MAX_CRASH_RESTARTS - 1),
+            ExitResponse::Crash { restart_attempt: Some(MAX_CRASH_RESTARTS) }
+        );
+        assert_eq!(exit_response(false, true, MAX_CRASH_RESTARTS), ExitResponse::Crash { restart_attempt: None });
+    }
+}